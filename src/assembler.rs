@@ -0,0 +1,529 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::{constants::ROM_START_ADDRESS, instruction_parser::InstructionType};
+
+/// How deeply `include`s can nest before we assume a file is including itself.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Assembles source written with the same mnemonics the disassembler prints in its Cowgod
+/// syntax, e.g. `LD V3, 0x21` or `DRW V5, V9, 10`, into a ROM that loads at `0x200`.
+///
+/// Besides instructions, a line can hold:
+/// - a label, `name:`, which can be followed by an instruction on the same line
+/// - a constant, `name EQU value`
+/// - data, `DB 1, 2, 3` for bytes or `DW 0x1234` for big-endian words
+/// - `INCLUDE "other.asm"`, which pastes in another file, relative to this one
+///
+/// Numbers can be decimal, hex (`0x`, `$`, or `#`), or binary (`0b`), and anywhere a number is
+/// expected a label or constant can be used instead. Everything after a `;` is a comment.
+/// Since `source` has no file of its own, its `include`s are resolved relative to the current
+/// directory.
+pub fn assemble(source: &str) -> Result<Vec<u8>> {
+    let mut lines = vec![];
+    read_lines(source, Rc::from("<input>"), Path::new("."), 0, &mut lines)?;
+    assemble_lines(&lines)
+}
+
+/// Assembles the file at `path`, resolving its `include`s relative to it.
+pub fn assemble_file(path: &Path) -> Result<Vec<u8>> {
+    let mut lines = vec![];
+    include(path, 0, &mut lines)?;
+    assemble_lines(&lines)
+}
+
+/// A line of source, remembering where it came from for error messages.
+struct SourceLine {
+    file: Rc<str>,
+    number: usize,
+    text: String,
+}
+
+impl SourceLine {
+    fn location(&self) -> String {
+        format!("{}:{}", self.file, self.number)
+    }
+}
+
+fn include(path: &Path, depth: usize, lines: &mut Vec<SourceLine>) -> Result<()> {
+    if depth > MAX_INCLUDE_DEPTH {
+        return Err(anyhow!(
+            "Includes are nested more than {} deep at {}",
+            MAX_INCLUDE_DEPTH,
+            path.display()
+        ));
+    }
+
+    let source =
+        fs::read_to_string(path).with_context(|| format!("Couldn't open {}", path.display()))?;
+    let directory = path.parent().unwrap_or(Path::new("."));
+    read_lines(
+        &source,
+        Rc::from(path.display().to_string()),
+        directory,
+        depth,
+        lines,
+    )
+}
+
+/// Splits source into lines, replacing `include` lines with the lines of the included file.
+fn read_lines(
+    source: &str,
+    file: Rc<str>,
+    directory: &Path,
+    depth: usize,
+    lines: &mut Vec<SourceLine>,
+) -> Result<()> {
+    for (i, text) in source.lines().enumerate() {
+        let code = strip_comment(text);
+        let mut words = code.splitn(2, char::is_whitespace);
+
+        if words
+            .next()
+            .is_some_and(|word| word.eq_ignore_ascii_case("include"))
+        {
+            let location = format!("{}:{}", file, i + 1);
+            let included = words
+                .next()
+                .map(str::trim)
+                .and_then(|path| path.strip_prefix('"')?.strip_suffix('"'))
+                .ok_or_else(|| anyhow!("{}: expected INCLUDE \"path\"", location))?;
+
+            include(&directory.join(included), depth + 1, lines)
+                .with_context(|| format!("{}: in include", location))?;
+        } else {
+            lines.push(SourceLine {
+                file: file.clone(),
+                number: i + 1,
+                text: code.to_string(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+fn strip_comment(line: &str) -> &str {
+    line.split(';').next().unwrap_or_default().trim()
+}
+
+/// A line with its label taken off, split into a mnemonic or directive and its operands.
+struct Statement<'a> {
+    mnemonic: String,
+    operands: Vec<Operand<'a>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operand<'a> {
+    /// V0 to VF
+    Register(u8),
+    /// One of the special operands of `LD` and `ADD`: I, [I], DT, ST, K, F, HF, B, or R
+    Keyword(&'a str),
+    /// `LONG address`, for XO-CHIP's 16-bit `LD I`
+    Long(&'a str),
+    /// A number, label, or constant
+    Value(&'a str),
+}
+
+impl<'a> Operand<'a> {
+    fn parse(text: &'a str) -> Self {
+        const KEYWORDS: [&str; 9] = ["I", "[I]", "DT", "ST", "K", "F", "HF", "B", "R"];
+
+        if let Some(register) = parse_register(text) {
+            return Operand::Register(register);
+        }
+        if let Some(&keyword) = KEYWORDS.iter().find(|k| k.eq_ignore_ascii_case(text)) {
+            return Operand::Keyword(keyword);
+        }
+        match text.split_once(char::is_whitespace) {
+            Some((long, address)) if long.eq_ignore_ascii_case("long") => {
+                Operand::Long(address.trim())
+            }
+            _ => Operand::Value(text),
+        }
+    }
+}
+
+fn parse_register(text: &str) -> Option<u8> {
+    let digit = text.strip_prefix(['V', 'v'])?;
+    if digit.len() != 1 {
+        return None;
+    }
+    u8::from_str_radix(digit, 16).ok()
+}
+
+fn parse_number(text: &str) -> Option<u16> {
+    let lowercase = text.to_ascii_lowercase();
+    if let Some(hex) = lowercase
+        .strip_prefix("0x")
+        .or_else(|| lowercase.strip_prefix('$'))
+        .or_else(|| lowercase.strip_prefix('#'))
+    {
+        u16::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = lowercase.strip_prefix("0b") {
+        u16::from_str_radix(binary, 2).ok()
+    } else {
+        lowercase.parse().ok()
+    }
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Takes the label, if any, off the front of a line.
+fn split_label(text: &str) -> (Option<&str>, &str) {
+    match text.split_once(':') {
+        Some((label, rest)) if is_identifier(label.trim()) => (Some(label.trim()), rest.trim()),
+        _ => (None, text),
+    }
+}
+
+/// Splits `name EQU value` into its name and value.
+fn parse_constant(text: &str) -> Option<(&str, &str)> {
+    let (name, rest) = text.split_once(char::is_whitespace)?;
+    let (equ, value) = rest.trim().split_once(char::is_whitespace)?;
+    (equ.eq_ignore_ascii_case("equ") && is_identifier(name)).then_some((name, value.trim()))
+}
+
+fn parse_statement(text: &str) -> Option<Statement<'_>> {
+    if text.is_empty() {
+        return None;
+    }
+
+    let (mnemonic, operands) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    let operands = if operands.trim().is_empty() {
+        vec![]
+    } else {
+        operands
+            .split(',')
+            .map(|operand| Operand::parse(operand.trim()))
+            .collect()
+    };
+
+    Some(Statement {
+        mnemonic: mnemonic.to_ascii_uppercase(),
+        operands,
+    })
+}
+
+/// Names defined by labels and `EQU`.
+#[derive(Default)]
+struct Symbols(HashMap<String, u16>);
+
+impl Symbols {
+    fn define(&mut self, name: &str, value: u16) -> Result<()> {
+        if self.0.insert(name.to_string(), value).is_some() {
+            return Err(anyhow!("{} is already defined", name));
+        }
+        Ok(())
+    }
+
+    fn resolve(&self, text: &str) -> Result<u16> {
+        parse_number(text)
+            .or_else(|| self.0.get(text).copied())
+            .ok_or_else(|| anyhow!("{} is not a number, label, or constant", text))
+    }
+
+    /// Resolves an operand that must be a value no wider than `bits`.
+    fn value(&self, operand: Operand, bits: u32) -> Result<u16> {
+        let Operand::Value(text) = operand else {
+            return Err(anyhow!("Expected a value, got {:?}", operand));
+        };
+        let value = self.resolve(text)?;
+        if bits < 16 && value >= 1 << bits {
+            return Err(anyhow!("{} doesn't fit in {} bits", text, bits));
+        }
+        Ok(value)
+    }
+}
+
+fn assemble_lines(lines: &[SourceLine]) -> Result<Vec<u8>> {
+    // First pass: work out where every label ends up, and what every constant is
+    let mut symbols = Symbols::default();
+    let mut address = ROM_START_ADDRESS;
+    for line in lines {
+        first_pass(line, &mut symbols, &mut address).with_context(|| line.location())?;
+    }
+
+    // Second pass: now that every label is known, emit the bytes
+    let mut rom = vec![];
+    for line in lines {
+        let (_, text) = split_label(&line.text);
+        if parse_constant(text).is_some() {
+            continue;
+        }
+        let Some(statement) = parse_statement(text) else {
+            continue;
+        };
+        encode_statement(&statement, &symbols, &mut rom)
+            .with_context(|| format!("{}: {}", line.location(), line.text))?;
+    }
+
+    Ok(rom)
+}
+
+fn first_pass(line: &SourceLine, symbols: &mut Symbols, address: &mut u16) -> Result<()> {
+    let (label, text) = split_label(&line.text);
+    if let Some(label) = label {
+        symbols.define(label, *address)?;
+    }
+
+    if let Some((name, value)) = parse_constant(text) {
+        let value = symbols.resolve(value)?;
+        return symbols.define(name, value);
+    }
+
+    if let Some(statement) = parse_statement(text) {
+        *address = address
+            .checked_add(statement_size(&statement))
+            .ok_or_else(|| anyhow!("Program is too large"))?;
+    }
+    Ok(())
+}
+
+/// How many bytes a statement assembles to, which the first pass needs before labels are known.
+fn statement_size(statement: &Statement) -> u16 {
+    match (statement.mnemonic.as_str(), statement.operands.as_slice()) {
+        ("DB", operands) => operands.len() as u16,
+        ("DW", operands) => operands.len() as u16 * 2,
+        ("LD", [_, Operand::Long(_)]) => 4,
+        _ => 2,
+    }
+}
+
+fn encode_statement(statement: &Statement, symbols: &Symbols, rom: &mut Vec<u8>) -> Result<()> {
+    use InstructionType::*;
+    use Operand::*;
+
+    let nnn = |operand| symbols.value(operand, 12);
+    let nn = |operand| symbols.value(operand, 8).map(|value| value as u8);
+    let n = |operand| symbols.value(operand, 4).map(|value| value as u8);
+
+    let instr = match (statement.mnemonic.as_str(), statement.operands.as_slice()) {
+        ("DB", operands) => {
+            for &operand in operands {
+                rom.push(nn(operand)?);
+            }
+            return Ok(());
+        }
+        ("DW", operands) => {
+            for &operand in operands {
+                rom.extend_from_slice(&symbols.value(operand, 16)?.to_be_bytes());
+            }
+            return Ok(());
+        }
+        ("LD", [Keyword("I"), Long(address)]) => {
+            rom.extend_from_slice(&SetIndexRegisterLong.encode().to_be_bytes());
+            rom.extend_from_slice(&symbols.value(Value(address), 16)?.to_be_bytes());
+            return Ok(());
+        }
+
+        ("CLS", []) => ClearScreen,
+        ("RET", []) => ReturnFromSubroutine,
+        ("SCD", [rows]) => ScrollDown(n(*rows)?),
+        ("SCU", [rows]) => ScrollUp(n(*rows)?),
+        ("SCR", []) => ScrollRight,
+        ("SCL", []) => ScrollLeft,
+        ("EXIT", []) => Exit,
+        ("LOW", []) => LowResolution,
+        ("HIGH", []) => HighResolution,
+        ("JP", [Register(0), address]) => JumpWithOffset(nnn(*address)?),
+        ("JP", [address]) => JumpToMemoryLocation(nnn(*address)?),
+        ("CALL", [address]) => CallSubroutine(nnn(*address)?),
+        ("SE", [Register(vx), Register(vy)]) => SkipIfRegistersEq { vx: *vx, vy: *vy },
+        ("SE", [Register(vx), value]) => SkipIfRegisterEqValue {
+            vx: *vx,
+            value: nn(*value)?,
+        },
+        ("SNE", [Register(vx), Register(vy)]) => SkipIfRegistersNeq { vx: *vx, vy: *vy },
+        ("SNE", [Register(vx), value]) => SkipIfRegisterNeqValue {
+            vx: *vx,
+            value: nn(*value)?,
+        },
+        ("SAVE", [Register(vx), Register(vy)]) => StoreRegisterRange { vx: *vx, vy: *vy },
+        ("LOAD", [Register(vx), Register(vy)]) => LoadRegisterRange { vx: *vx, vy: *vy },
+        ("LD", [Register(vx), Register(vy)]) => CopyRegister { vx: *vx, vy: *vy },
+        ("LD", [Register(vx), Keyword("DT")]) => FetchDelayTimerToVX(*vx),
+        ("LD", [Register(vx), Keyword("K")]) => WaitForKeyInVX(*vx),
+        ("LD", [Register(vx), Keyword("[I]")]) => LoadMemoryToVariableRegistersFromVXAddress(*vx),
+        ("LD", [Register(vx), Keyword("R")]) => LoadFlagsToVariableRegistersUpToVX(*vx),
+        ("LD", [Register(vx), value]) => UpdateRegister {
+            vx: *vx,
+            value: nn(*value)?,
+        },
+        ("LD", [Keyword("I"), address]) => SetIndexRegister(nnn(*address)?),
+        ("LD", [Keyword("DT"), Register(vx)]) => SetDelayTimerToVX(*vx),
+        ("LD", [Keyword("ST"), Register(vx)]) => SetSoundTimerToVX(*vx),
+        ("LD", [Keyword("F"), Register(vx)]) => SetIndexToFontCharInVX(*vx),
+        ("LD", [Keyword("HF"), Register(vx)]) => SetIndexToBigFontCharInVX(*vx),
+        ("LD", [Keyword("B"), Register(vx)]) => BinaryCodedDecimalConversionForVX(*vx),
+        ("LD", [Keyword("[I]"), Register(vx)]) => StoreVariableRegistersToMemoryUpToVX(*vx),
+        ("LD", [Keyword("R"), Register(vx)]) => StoreVariableRegistersToFlagsUpToVX(*vx),
+        ("ADD", [Keyword("I"), Register(vx)]) => AddToIndexFromVX(*vx),
+        ("ADD", [Register(vx), Register(vy)]) => AddRegisterToRegister { vx: *vx, vy: *vy },
+        ("ADD", [Register(vx), value]) => AddValueToRegister {
+            vx: *vx,
+            value: nn(*value)?,
+        },
+        ("OR", [Register(vx), Register(vy)]) => BitwiseOR { vx: *vx, vy: *vy },
+        ("AND", [Register(vx), Register(vy)]) => BitwiseAND { vx: *vx, vy: *vy },
+        ("XOR", [Register(vx), Register(vy)]) => BitwiseXOR { vx: *vx, vy: *vy },
+        ("SUB", [Register(vx), Register(vy)]) => SubtractXY { vx: *vx, vy: *vy },
+        ("SUBN", [Register(vx), Register(vy)]) => SubtractYX { vx: *vx, vy: *vy },
+        ("SHR", [Register(vx)]) => ShiftRight { vx: *vx, vy: *vx },
+        ("SHR", [Register(vx), Register(vy)]) => ShiftRight { vx: *vx, vy: *vy },
+        ("SHL", [Register(vx)]) => ShiftLeft { vx: *vx, vy: *vx },
+        ("SHL", [Register(vx), Register(vy)]) => ShiftLeft { vx: *vx, vy: *vy },
+        ("RND", [Register(vx), mask]) => GenerateRandomNumber {
+            vx: *vx,
+            bitmask: nn(*mask)?,
+        },
+        ("DRW", [Register(vx), Register(vy), rows]) => Display {
+            vx: *vx,
+            vy: *vy,
+            n: n(*rows)?,
+        },
+        ("SKP", [Register(vx)]) => SkipIfPressedVX(*vx),
+        ("SKNP", [Register(vx)]) => SkipIfNotPressedVX(*vx),
+        ("PLANE", [planes]) => SelectPlanes(n(*planes)?),
+        ("AUDIO", []) => LoadAudioPattern,
+        ("PITCH", [Register(vx)]) => SetPitchToVX(*vx),
+
+        (mnemonic, operands) => {
+            return Err(anyhow!(
+                "No {} instruction takes {} operand(s) like these",
+                mnemonic,
+                operands.len()
+            ))
+        }
+    };
+
+    rom.extend_from_slice(&instr.encode().to_be_bytes());
+    Ok(())
+}
+
+/// Where `chip8 asm` writes a ROM when no output path is given: next to the source, as `.ch8`.
+pub fn default_output_path(source_path: &Path) -> PathBuf {
+    source_path.with_extension("ch8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disassembler::{disassemble, disassemble_recursive, Line, LineKind, Syntax};
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    /// Turns a listing back into source, dropping the address and raw byte columns.
+    fn source_from_listing(lines: &[Line]) -> String {
+        let mut source = String::new();
+        for line in lines {
+            if let Some(label) = &line.label {
+                source += &format!("{}:\n", label);
+            }
+            source += &format!("{}\n", line.statement());
+        }
+        source
+    }
+
+    /// One of every instruction, followed by data and a sprite. Each instruction that ends a path
+    /// (RET, JP, EXIT, JP V0) comes right after a skip, so that following control flow still
+    /// reaches everything.
+    const EVERY_INSTRUCTION: [u16; 55] = [
+        0x00E0, 0x00C4, 0x00D3, 0x00FB, 0x00FC, 0x00FE, 0x00FF, 0x3AF1, 0x00EE, 0x4321, 0x1123,
+        0x2456, 0x5120, 0x00FD, 0x5352, 0x5A13, 0x6234, 0x7B00, 0x8380, 0x8501, 0x84C2, 0x8193,
+        0x8024, 0x8815, 0x8126, 0x8477, 0x8C5E, 0x9F40, 0xB357, 0xA987, 0xC801, 0xD59A, 0xE49E,
+        0xF000, 0x1234, 0xE8A1, 0xF201, 0xF002, 0xF407, 0xF20A, 0xFE15, 0xF018, 0xFF1E, 0xF729,
+        0xF330, 0xFD33, 0xF63A, 0xF455, 0xFA65, 0xF775, 0xF285, 0x0000, 0xFFFF, 0x3C7E, 0x8142,
+    ];
+
+    /// F000 and its address make a single instruction, and the linear disassembler also decodes
+    /// the sprite's bytes as instructions
+    #[rstest]
+    #[case::linear(disassemble, 52)]
+    #[case::recursive(disassemble_recursive, 50)]
+    fn disassembly_round_trips(
+        #[case] disassembler: fn(&[u8], Syntax) -> Vec<Line>,
+        #[case] instruction_count: usize,
+    ) {
+        let rom: Vec<u8> = EVERY_INSTRUCTION
+            .iter()
+            .flat_map(|word| word.to_be_bytes())
+            .collect();
+
+        let listing = disassembler(&rom, Syntax::Cowgod);
+        let source = source_from_listing(&listing);
+
+        assert_eq!(assemble(&source).unwrap(), rom);
+        let instructions = listing
+            .iter()
+            .filter(|line| matches!(line.kind, LineKind::Instruction(_)))
+            .count();
+        assert_eq!(instructions, instruction_count);
+    }
+
+    #[test]
+    fn labels_and_constants_resolve() {
+        let source = "
+            SPEED equ 3
+            start:  LD V0, SPEED   ; labels can be used before they are defined
+                    CALL draw
+                    JP start
+            draw:   LD I, sprite
+                    DRW V0, V1, 2
+                    RET
+            sprite: DB 0b00111100, $7E
+                    DW 0x1234
+        ";
+
+        assert_eq!(
+            assemble(source).unwrap(),
+            [
+                0x60, 0x03, 0x22, 0x06, 0x12, 0x00, 0xA2, 0x0C, 0xD0, 0x12, 0x00, 0xEE, 0x3C, 0x7E,
+                0x12, 0x34,
+            ]
+        );
+    }
+
+    #[test]
+    fn includes_are_relative_to_the_including_file() {
+        let directory = std::env::temp_dir().join(format!(
+            "chip8-{}-includes_are_relative_to_the_including_file",
+            std::process::id()
+        ));
+        fs::create_dir_all(directory.join("lib")).unwrap();
+        fs::write(
+            directory.join("main.asm"),
+            "CLS\nINCLUDE \"lib/sprites.asm\"\n",
+        )
+        .unwrap();
+        fs::write(directory.join("lib/sprites.asm"), "DB 0xFF ; a line\n").unwrap();
+
+        let rom = assemble_file(&directory.join("main.asm"));
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(rom.unwrap(), [0x00, 0xE0, 0xFF]);
+    }
+
+    #[rstest]
+    #[case::unknown_mnemonic("CLS\nFOO V1", "<input>:2")]
+    #[case::value_too_large("LD V1, 256", "256 doesn't fit in 8 bits")]
+    #[case::undefined_label("JP nowhere", "nowhere is not a number, label, or constant")]
+    #[case::duplicate_label("a: CLS\na: CLS", "a is already defined")]
+    fn errors_explain_what_went_wrong(#[case] source: &str, #[case] message: &str) {
+        let error = format!("{:#}", assemble(source).unwrap_err());
+
+        assert!(error.contains(message), "{}", error);
+    }
+}
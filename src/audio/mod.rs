@@ -0,0 +1,212 @@
+mod null;
+mod sdl;
+mod wav;
+
+use std::{f32::consts::TAU, path::Path};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use sdl2::Sdl;
+
+use crate::virtual_computer::{Sound, DEFAULT_PITCH};
+
+pub use null::NullAudioSink;
+pub use sdl::SdlAudioSink;
+pub use wav::WavAudioSink;
+
+/// Sample rate used by every sink.
+pub const SAMPLE_RATE: u32 = 44_100;
+
+/// Somewhere to send the buzzer's output. The emulator loop calls `update` once per 60hz frame
+/// with what should be playing until the next one.
+pub trait AudioSink {
+    fn update(&mut self, sound: Sound) -> Result<()>;
+
+    /// Flushes anything buffered once the emulator stops.
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AudioBackend {
+    /// The default audio device, through SDL
+    Sdl,
+
+    /// A WAV file, for headless runs
+    Wav,
+
+    /// No sound at all
+    Null,
+}
+
+impl AudioBackend {
+    /// Opens the sink. `sdl_context` must be present for the SDL backend, and `wav_path` is where
+    /// the WAV backend writes to.
+    pub fn create(
+        self,
+        sdl_context: Option<&Sdl>,
+        wav_path: &Path,
+        tone: ToneSettings,
+    ) -> Result<Box<dyn AudioSink>> {
+        Ok(match self {
+            AudioBackend::Sdl => Box::new(SdlAudioSink::new(
+                sdl_context.expect("SDL is initialized for the SDL audio backend"),
+                tone,
+            )?),
+            AudioBackend::Wav => Box::new(WavAudioSink::create(wav_path, tone)?),
+            AudioBackend::Null => Box::new(NullAudioSink),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Waveform {
+    Square,
+    Sine,
+    Triangle,
+    Sawtooth,
+}
+
+impl Waveform {
+    /// The waveform's value at `phase`, which goes from 0 to 1 over a single period.
+    fn sample(self, phase: f32) -> f32 {
+        match self {
+            Waveform::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Sine => (phase * TAU).sin(),
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Waveform::Sawtooth => 2.0 * phase - 1.0,
+        }
+    }
+}
+
+/// How the plain CHIP-8 buzzer sounds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ToneSettings {
+    /// In hertz
+    pub frequency: f32,
+    pub waveform: Waveform,
+    /// From 0 (silent) to 1 (full scale)
+    pub volume: f32,
+}
+
+impl Default for ToneSettings {
+    fn default() -> Self {
+        Self {
+            frequency: 440.0,
+            waveform: Waveform::Square,
+            volume: 0.25,
+        }
+    }
+}
+
+/// Turns `Sound`s into samples. Keeps track of where it is in the waveform between calls so
+/// consecutive buffers join up without clicks.
+pub struct ToneGenerator {
+    settings: ToneSettings,
+    /// How far through the current period (for tones) or the pattern (for XO-CHIP), from 0 to 1
+    phase: f32,
+}
+
+impl ToneGenerator {
+    pub fn new(settings: ToneSettings) -> Self {
+        Self {
+            settings,
+            phase: 0.0,
+        }
+    }
+
+    /// Fills `out` with mono samples at `SAMPLE_RATE`.
+    pub fn fill(&mut self, sound: Sound, out: &mut [f32]) {
+        match sound {
+            Sound::Silent => {
+                out.fill(0.0);
+                self.phase = 0.0;
+            }
+            Sound::Tone => {
+                let step = self.settings.frequency / SAMPLE_RATE as f32;
+                for sample in out {
+                    *sample = self.settings.waveform.sample(self.phase) * self.settings.volume;
+                    self.phase = (self.phase + step).fract();
+                }
+            }
+            Sound::Pattern { pattern, pitch } => {
+                let bit_count = pattern.len() * 8;
+                let step = pattern_playback_rate(pitch) / SAMPLE_RATE as f32 / bit_count as f32;
+                for sample in out {
+                    let bit = (self.phase * bit_count as f32) as usize;
+                    let is_set = pattern[bit / 8] & (0x80 >> (bit % 8)) != 0;
+
+                    *sample = if is_set { 1.0 } else { -1.0 } * self.settings.volume;
+                    self.phase = (self.phase + step).fract();
+                }
+            }
+        }
+    }
+}
+
+/// How many of an XO-CHIP audio pattern's bits play per second at the given pitch.
+fn pattern_playback_rate(pitch: u8) -> f32 {
+    4000.0 * 2f32.powf((pitch as f32 - DEFAULT_PITCH as f32) / 48.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn default_pitch_plays_4000_bits_per_second() {
+        assert_eq!(pattern_playback_rate(DEFAULT_PITCH), 4000.0);
+        assert_eq!(pattern_playback_rate(DEFAULT_PITCH + 48), 8000.0);
+    }
+
+    #[test]
+    fn square_tone_alternates_at_frequency() {
+        let mut generator = ToneGenerator::new(ToneSettings {
+            frequency: SAMPLE_RATE as f32 / 4.0,
+            waveform: Waveform::Square,
+            volume: 0.5,
+        });
+        let mut out = [0.0; 8];
+        generator.fill(Sound::Tone, &mut out);
+
+        assert_eq!(out, [0.5, 0.5, -0.5, -0.5, 0.5, 0.5, -0.5, -0.5]);
+    }
+
+    #[test]
+    fn pattern_plays_its_bits() {
+        let mut generator = ToneGenerator::new(ToneSettings::default());
+        let mut pattern = [0; 16];
+        pattern[0] = 0b1000_0000;
+
+        // 8000 bits per second, so each bit lasts for about 5.5 samples
+        let mut out = [0.0; 22];
+        generator.fill(
+            Sound::Pattern {
+                pattern,
+                pitch: DEFAULT_PITCH + 48,
+            },
+            &mut out,
+        );
+        let volume = ToneSettings::default().volume;
+
+        assert!(out[..5].iter().all(|&sample| sample == volume));
+        assert!(out[6..].iter().all(|&sample| sample == -volume));
+    }
+
+    #[test]
+    fn silence_is_silent() {
+        let mut generator = ToneGenerator::new(ToneSettings::default());
+        let mut out = [1.0; 4];
+        generator.fill(Sound::Silent, &mut out);
+
+        assert_eq!(out, [0.0; 4]);
+    }
+}
@@ -0,0 +1,13 @@
+use anyhow::Result;
+
+use super::AudioSink;
+use crate::virtual_computer::Sound;
+
+/// Discards all sound.
+pub struct NullAudioSink;
+
+impl AudioSink for NullAudioSink {
+    fn update(&mut self, _sound: Sound) -> Result<()> {
+        Ok(())
+    }
+}
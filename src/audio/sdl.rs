@@ -0,0 +1,67 @@
+use anyhow::{anyhow, Result};
+use sdl2::{
+    audio::{AudioCallback, AudioDevice, AudioSpecDesired},
+    Sdl,
+};
+
+use super::{AudioSink, ToneGenerator, ToneSettings, SAMPLE_RATE};
+use crate::virtual_computer::Sound;
+
+/// Plays through the default audio device. SDL pulls samples on its own thread, so `update`
+/// only swaps out what the callback is generating.
+pub struct SdlAudioSink {
+    device: AudioDevice<Buzzer>,
+}
+
+struct Buzzer {
+    generator: ToneGenerator,
+    sound: Sound,
+}
+
+impl AudioCallback for Buzzer {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        self.generator.fill(self.sound, out);
+    }
+}
+
+impl SdlAudioSink {
+    pub fn new(sdl_context: &Sdl, tone: ToneSettings) -> Result<Self> {
+        let audio_subsystem = sdl_context
+            .audio()
+            .map_err(|e| anyhow!("Couldn't start SDL audio: {}", e))?;
+
+        let desired_spec = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE as i32),
+            channels: Some(1),
+            samples: Some(512),
+        };
+
+        let device = audio_subsystem
+            .open_playback(None, &desired_spec, |spec| {
+                if spec.freq != SAMPLE_RATE as i32 {
+                    eprintln!(
+                        "Audio device runs at {}hz instead of {}hz, pitch will be off",
+                        spec.freq, SAMPLE_RATE
+                    );
+                }
+
+                Buzzer {
+                    generator: ToneGenerator::new(tone),
+                    sound: Sound::Silent,
+                }
+            })
+            .map_err(|e| anyhow!("Couldn't open the audio device: {}", e))?;
+        device.resume();
+
+        Ok(Self { device })
+    }
+}
+
+impl AudioSink for SdlAudioSink {
+    fn update(&mut self, sound: Sound) -> Result<()> {
+        self.device.lock().sound = sound;
+        Ok(())
+    }
+}
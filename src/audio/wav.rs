@@ -0,0 +1,148 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use anyhow::Result;
+
+use super::{AudioSink, ToneGenerator, ToneSettings, SAMPLE_RATE};
+use crate::virtual_computer::Sound;
+
+/// Samples generated per 60hz frame.
+const SAMPLES_PER_FRAME: usize = SAMPLE_RATE as usize / 60;
+
+/// Size of the RIFF and format chunk headers that come before the samples.
+const HEADER_SIZE: u32 = 44;
+
+/// Writes everything the buzzer plays to a 16-bit mono WAV file, one frame's worth of samples
+/// per `update`. The file is only valid once `finish` has filled in the chunk sizes, which also
+/// happens on drop so that runs ending in an error still leave a playable file.
+pub struct WavAudioSink {
+    writer: BufWriter<File>,
+    generator: ToneGenerator,
+    samples_written: u32,
+    finished: bool,
+}
+
+impl WavAudioSink {
+    pub fn create(path: &Path, tone: ToneSettings) -> Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        write_header(&mut writer, 0)?;
+
+        Ok(Self {
+            writer,
+            generator: ToneGenerator::new(tone),
+            samples_written: 0,
+            finished: false,
+        })
+    }
+}
+
+impl AudioSink for WavAudioSink {
+    fn update(&mut self, sound: Sound) -> Result<()> {
+        let mut samples = [0.0; SAMPLES_PER_FRAME];
+        self.generator.fill(sound, &mut samples);
+
+        for sample in samples {
+            let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            self.writer.write_all(&sample.to_le_bytes())?;
+        }
+        self.samples_written += SAMPLES_PER_FRAME as u32;
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.finished = true;
+        self.writer.seek(SeekFrom::Start(0))?;
+        write_header(&mut self.writer, self.samples_written * 2)?;
+        // Back to the end, so that anything played afterwards is appended rather than written
+        // over the first samples
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+
+        Ok(())
+    }
+}
+
+impl Drop for WavAudioSink {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.finish();
+        }
+    }
+}
+
+fn write_header(writer: &mut impl Write, data_size: u32) -> Result<()> {
+    let channels: u16 = 1;
+    let bits_per_sample: u16 = 16;
+    let block_align = channels * bits_per_sample / 8;
+    let byte_rate = SAMPLE_RATE * block_align as u32;
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(HEADER_SIZE - 8 + data_size).to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?;
+    writer.write_all(&1u16.to_le_bytes())?; // PCM
+    writer.write_all(&channels.to_le_bytes())?;
+    writer.write_all(&SAMPLE_RATE.to_le_bytes())?;
+    writer.write_all(&byte_rate.to_le_bytes())?;
+    writer.write_all(&block_align.to_le_bytes())?;
+    writer.write_all(&bits_per_sample.to_le_bytes())?;
+
+    writer.write_all(b"data")?;
+    writer.write_all(&data_size.to_le_bytes())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+
+    #[test]
+    fn playing_after_finish_appends_samples() {
+        let path = std::env::temp_dir().join(format!(
+            "chip8-{}-playing_after_finish_appends_samples.wav",
+            std::process::id()
+        ));
+        let mut sink = WavAudioSink::create(&path, ToneSettings::default()).unwrap();
+        sink.update(Sound::Tone).unwrap();
+        sink.finish().unwrap();
+        sink.update(Sound::Silent).unwrap();
+        sink.finish().unwrap();
+        drop(sink);
+
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let data_size = (SAMPLES_PER_FRAME * 4) as u32;
+        assert_eq!(bytes.len(), HEADER_SIZE as usize + data_size as usize);
+        assert_eq!(bytes[40..44], data_size.to_le_bytes());
+        assert_ne!(bytes[44..46], [0, 0]);
+    }
+
+    #[test]
+    fn dropping_without_finishing_still_writes_the_sizes() {
+        let path = std::env::temp_dir().join(format!(
+            "chip8-{}-dropping_without_finishing_still_writes_the_sizes.wav",
+            std::process::id()
+        ));
+        let mut sink = WavAudioSink::create(&path, ToneSettings::default()).unwrap();
+        sink.update(Sound::Tone).unwrap();
+        drop(sink);
+
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let data_size = (SAMPLES_PER_FRAME * 2) as u32;
+        assert_eq!(bytes.len(), HEADER_SIZE as usize + data_size as usize);
+        assert_eq!(bytes[4..8], (HEADER_SIZE - 8 + data_size).to_le_bytes());
+        assert_eq!(bytes[40..44], data_size.to_le_bytes());
+    }
+}
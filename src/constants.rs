@@ -1,16 +1,20 @@
 use sdl2::pixels::Color;
 
-pub const WINDOW_WIDTH: u32 = 896;
-pub const WINDOW_HEIGHT: u32 = 448;
-
 pub const DISPLAY_WIDTH: u8 = 64;
 pub const DISPLAY_HEIGHT: u8 = 32;
 
-pub const PIXEL_WIDTH: u8 = (WINDOW_WIDTH / DISPLAY_WIDTH as u32) as u8;
-pub const PIXEL_HEIGHT: u8 = (WINDOW_HEIGHT / DISPLAY_HEIGHT as u32) as u8;
+/// The SUPER-CHIP high resolution mode
+pub const HIRES_DISPLAY_WIDTH: u8 = 128;
+pub const HIRES_DISPLAY_HEIGHT: u8 = 64;
 
-pub const PIXEL_COLOR: &Color = &Color::RGB(255, 255, 255);
-pub const BACKGROUND_COLOR: &Color = &Color::RGB(0, 0, 0);
+/// The color of each pixel value: no planes lit (the background), the first plane, the second
+/// plane, and both planes. Only XO-CHIP programs ever use the last two.
+pub const PALETTE: &[Color; 4] = &[
+    Color::RGB(0, 0, 0),
+    Color::RGB(255, 255, 255),
+    Color::RGB(255, 102, 0),
+    Color::RGB(102, 34, 0),
+];
 
 pub const FONT_STARTING_MEMORY_ADDRESS: &u8 = &0x50;
 pub const FONT_DATA: &[u8; 80] = &[
@@ -31,3 +35,39 @@ pub const FONT_DATA: &[u8; 80] = &[
     0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
+
+/// SUPER-CHIP's 8x10 digits, extended with A-F the way Octo does
+pub const BIG_FONT_STARTING_MEMORY_ADDRESS: &u16 = &0xA0;
+pub const BIG_FONT_DATA: &[u8; 160] = &[
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+/// XO-CHIP programs can address a full 64KB of memory, everything else gets 4KB
+pub const MEMORY_SIZE: usize = 0x1000;
+pub const XO_CHIP_MEMORY_SIZE: usize = 0x10000;
+
+/// Where ROMs are loaded and execution starts. Everything below is reserved for the interpreter.
+pub const ROM_START_ADDRESS: u16 = 0x200;
+
+/// How many return addresses fit on the stack before 2NNN overflows it. The COSMAC VIP only set
+/// aside room for 12, everything after it has 16.
+pub const STACK_DEPTH: usize = 16;
+pub const VIP_STACK_DEPTH: usize = 12;
+
+/// How many of the RPL calculator's user flags FX75 and FX85 can save and restore
+pub const RPL_FLAG_COUNT: usize = 16;
@@ -0,0 +1,431 @@
+use std::{
+    collections::BTreeSet,
+    io::{BufRead, Write},
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    errors::Chip8Error, instruction_parser::parse_instruction, virtual_computer::VirtualComputer,
+};
+
+const HELP: &str = "\
+Commands:
+  s, step [N]          execute N instructions (default 1)
+  c, continue          run until a breakpoint or watchpoint is hit
+  skip                 move past the next instruction without executing it, e.g. after a fault
+  b, break ADDR        pause before executing the instruction at ADDR
+  d, delete ADDR       remove the breakpoint at ADDR
+  w, watch TARGET      pause when TARGET changes: a memory ADDR, V0-VF, or I
+  u, unwatch TARGET    remove a watchpoint
+  r, regs              print V0-VF, I, PC, the stack, and the timers
+  x ADDR [LEN]         hex dump LEN bytes of memory starting at ADDR (default 16)
+  q, quit              stop the emulator
+Addresses are hexadecimal, with or without a 0x prefix.";
+
+/// Something the debugger can watch for changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Watchpoint {
+    Memory(u16),
+    Register(u8),
+    Index,
+}
+
+impl Watchpoint {
+    fn parse(target: &str) -> Result<Self> {
+        let lowercase = target.to_ascii_lowercase();
+        if lowercase == "i" {
+            return Ok(Watchpoint::Index);
+        }
+        if let Some(register) = lowercase.strip_prefix('v') {
+            return match u8::from_str_radix(register, 16) {
+                Ok(register) if register < 16 => Ok(Watchpoint::Register(register)),
+                _ => Err(anyhow!("{} is not a register", target)),
+            };
+        }
+        Ok(Watchpoint::Memory(parse_address(target)?))
+    }
+
+    fn value(self, vc: &VirtualComputer) -> u16 {
+        match self {
+            Watchpoint::Memory(address) => vc
+                .memory()
+                .get(address as usize)
+                .copied()
+                .unwrap_or_default() as u16,
+            Watchpoint::Register(register) => vc.variable_registers()[register as usize] as u16,
+            Watchpoint::Index => vc.index_register(),
+        }
+    }
+}
+
+impl std::fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Watchpoint::Memory(address) => write!(f, "[{:#05X}]", address),
+            Watchpoint::Register(register) => write!(f, "V{:X}", register),
+            Watchpoint::Index => write!(f, "I"),
+        }
+    }
+}
+
+/// Whether the emulator should keep going after the debugger hands control back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebuggerAction {
+    Run,
+    Quit,
+}
+
+/// Drives a `VirtualComputer` one instruction at a time in place of `VirtualComputer::run_frame`,
+/// dropping into a command prompt whenever execution is paused. The prompt reads commands from
+/// `input` and writes everything to `output`, so the core can be debugged without a window.
+pub struct Debugger<R, W> {
+    input: R,
+    output: W,
+    breakpoints: BTreeSet<u16>,
+    watchpoints: BTreeSet<Watchpoint>,
+    paused: bool,
+    /// Instructions left to run before pausing again, while stepping
+    steps_remaining: Option<u32>,
+    /// Frames can be interrupted part way through, so this is kept between calls to `run_frame`
+    executed_this_frame: u32,
+}
+
+impl<R: BufRead, W: Write> Debugger<R, W> {
+    /// Creates a debugger that starts out paused, so breakpoints can be set before the program
+    /// runs.
+    pub fn new(input: R, output: W) -> Self {
+        Self {
+            input,
+            output,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+            paused: true,
+            steps_remaining: None,
+            executed_this_frame: 0,
+        }
+    }
+
+    /// Runs the rest of the current 60hz frame, stopping at the prompt whenever a breakpoint or
+    /// watchpoint is hit or a step finishes. The timers tick once the frame's instructions have
+    /// all run, exactly as they would without the debugger.
+    pub fn run_frame(
+        &mut self,
+        vc: &mut VirtualComputer,
+        instructions_per_frame: u32,
+    ) -> Result<DebuggerAction> {
+        while self.executed_this_frame < instructions_per_frame {
+            if self.paused && self.prompt(vc)? == DebuggerAction::Quit {
+                return Ok(DebuggerAction::Quit);
+            }
+
+            let watched_before: Vec<u16> = self.watchpoints.iter().map(|w| w.value(vc)).collect();
+            let address = vc.program_counter();
+            if let Err(fault) = vc.step() {
+                writeln!(self.output, "{}", fault)?;
+                self.pause();
+                continue;
+            }
+            self.executed_this_frame += 1;
+
+            if vc.has_exited() {
+                writeln!(self.output, "Program exited at {:#05X}", address)?;
+                break;
+            }
+
+            let mut watch_hit = false;
+            for (watchpoint, before) in self.watchpoints.iter().zip(watched_before) {
+                let after = watchpoint.value(vc);
+                if after != before {
+                    writeln!(
+                        self.output,
+                        "Watchpoint {}: {:#X} -> {:#X} at {:#05X}",
+                        watchpoint, before, after, address
+                    )?;
+                    watch_hit = true;
+                }
+            }
+            if watch_hit {
+                self.pause();
+            }
+
+            if let Some(steps) = self.steps_remaining.as_mut() {
+                *steps -= 1;
+                if *steps == 0 {
+                    self.pause();
+                }
+            }
+
+            if !self.paused && self.breakpoints.contains(&vc.program_counter()) {
+                writeln!(self.output, "Breakpoint at {:#05X}", vc.program_counter())?;
+                self.pause();
+            }
+        }
+
+        self.executed_this_frame = 0;
+        vc.decrement_timers();
+        Ok(DebuggerAction::Run)
+    }
+
+    /// Reports a fault raised while the debugger wasn't driving the computer, and pauses so the
+    /// faulting instruction can be inspected at the next prompt.
+    pub fn break_on_fault(&mut self, fault: Chip8Error) -> Result<()> {
+        writeln!(self.output, "{}", fault)?;
+        self.pause();
+        Ok(())
+    }
+
+    fn pause(&mut self) {
+        self.paused = true;
+        self.steps_remaining = None;
+    }
+
+    /// Reads and runs commands until one of them resumes execution.
+    fn prompt(&mut self, vc: &mut VirtualComputer) -> Result<DebuggerAction> {
+        self.print_next_instruction(vc)?;
+
+        loop {
+            write!(self.output, "(chip8) ")?;
+            self.output.flush()?;
+
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                return Ok(DebuggerAction::Quit);
+            }
+
+            let args: Vec<&str> = line.split_whitespace().collect();
+            let Some((&command, args)) = args.split_first() else {
+                continue;
+            };
+
+            match self.run_command(vc, command, args) {
+                Ok(Some(action)) => return Ok(action),
+                Ok(None) => {}
+                Err(e) => writeln!(self.output, "{}", e)?,
+            }
+        }
+    }
+
+    /// Runs a single command, returning the action to take if it resumes execution.
+    fn run_command(
+        &mut self,
+        vc: &mut VirtualComputer,
+        command: &str,
+        args: &[&str],
+    ) -> Result<Option<DebuggerAction>> {
+        match (command, args) {
+            ("s" | "step", []) => self.step(1),
+            ("s" | "step", [count]) => self.step(
+                count
+                    .parse()
+                    .map_err(|_| anyhow!("{} is not a number of steps", count))?,
+            ),
+            ("c" | "continue", []) => {
+                self.paused = false;
+                return Ok(Some(DebuggerAction::Run));
+            }
+            // Continuing from a fault would only run into it again
+            ("skip", []) => {
+                vc.skip_faulting_instruction();
+                self.print_next_instruction(vc)?;
+            }
+            ("b" | "break", [address]) => {
+                self.breakpoints.insert(parse_address(address)?);
+            }
+            ("d" | "delete", [address]) => {
+                if !self.breakpoints.remove(&parse_address(address)?) {
+                    return Err(anyhow!("No breakpoint at {}", address));
+                }
+            }
+            ("w" | "watch", [target]) => {
+                self.watchpoints.insert(Watchpoint::parse(target)?);
+            }
+            ("u" | "unwatch", [target]) => {
+                if !self.watchpoints.remove(&Watchpoint::parse(target)?) {
+                    return Err(anyhow!("No watchpoint on {}", target));
+                }
+            }
+            ("r" | "regs", []) => self.print_registers(vc)?,
+            ("x", [address]) => self.hex_dump(vc, parse_address(address)?, 16)?,
+            ("x", [address, len]) => self.hex_dump(
+                vc,
+                parse_address(address)?,
+                len.parse()
+                    .map_err(|_| anyhow!("{} is not a number of bytes", len))?,
+            )?,
+            ("q" | "quit", []) => return Ok(Some(DebuggerAction::Quit)),
+            ("h" | "help", []) => writeln!(self.output, "{}", HELP)?,
+            _ => return Err(anyhow!("Unknown command, try `help`")),
+        }
+
+        Ok(if self.paused {
+            None
+        } else {
+            Some(DebuggerAction::Run)
+        })
+    }
+
+    fn step(&mut self, count: u32) {
+        if count > 0 {
+            self.paused = false;
+            self.steps_remaining = Some(count);
+        }
+    }
+
+    fn print_next_instruction(&mut self, vc: &VirtualComputer) -> Result<()> {
+        let pc = vc.program_counter() as usize;
+        match vc.memory().get(pc..pc + 2) {
+            Some(&[high, low]) => {
+                let raw = u16::from_be_bytes([high, low]);
+                match parse_instruction(raw) {
+                    Some(instr) => writeln!(self.output, "{:#05X}  {:04X}  {:?}", pc, raw, instr)?,
+                    None => writeln!(self.output, "{:#05X}  {:04X}  (unknown)", pc, raw)?,
+                }
+            }
+            _ => writeln!(self.output, "{:#05X}  (out of memory)", pc)?,
+        }
+        Ok(())
+    }
+
+    fn print_registers(&mut self, vc: &VirtualComputer) -> Result<()> {
+        for (row, registers) in vc.variable_registers().chunks(8).enumerate() {
+            let line: Vec<String> = registers
+                .iter()
+                .enumerate()
+                .map(|(i, value)| format!("V{:X}={:02X}", row * 8 + i, value))
+                .collect();
+            writeln!(self.output, "{}", line.join(" "))?;
+        }
+
+        let stack: Vec<String> = vc.stack().iter().map(|a| format!("{:03X}", a)).collect();
+        writeln!(
+            self.output,
+            "PC={:03X} I={:03X} DT={:02X} ST={:02X}",
+            vc.program_counter(),
+            vc.index_register(),
+            vc.delay_timer(),
+            vc.sound_timer()
+        )?;
+        writeln!(self.output, "Stack: [{}]", stack.join(" "))?;
+        Ok(())
+    }
+
+    fn hex_dump(&mut self, vc: &VirtualComputer, address: u16, len: usize) -> Result<()> {
+        let start = address as usize;
+        let end = start.saturating_add(len).min(vc.memory().len());
+        if start >= end {
+            return Err(anyhow!("{:#X} is outside of memory", address));
+        }
+
+        for (i, line) in vc.memory()[start..end].chunks(16).enumerate() {
+            let bytes: Vec<String> = line.iter().map(|b| format!("{:02X}", b)).collect();
+            writeln!(self.output, "{:04X}: {}", start + i * 16, bytes.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_address(text: &str) -> Result<u16> {
+    let digits = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .unwrap_or(text);
+    u16::from_str_radix(digits, 16).map_err(|_| anyhow!("{} is not an address", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::virtual_computer::CompatibilityMode;
+    use pretty_assertions::assert_eq;
+
+    /// Runs frames with the given commands typed at the prompt, returning everything printed.
+    fn debug(rom: &[u8], commands: &str, frames: usize) -> (VirtualComputer, String) {
+        let mut vc = VirtualComputer::from_rom_bytes(rom, CompatibilityMode::CosmacVIP).unwrap();
+        let mut output = vec![];
+        let mut debugger = Debugger::new(commands.as_bytes(), &mut output);
+
+        for _ in 0..frames {
+            if debugger.run_frame(&mut vc, 10).unwrap() == DebuggerAction::Quit {
+                break;
+            }
+        }
+
+        (vc, String::from_utf8(output).unwrap())
+    }
+
+    // V0 += 1, V1 += 2, loop
+    const COUNTER: [u8; 6] = [0x70, 0x01, 0x71, 0x02, 0x12, 0x00];
+
+    #[test]
+    fn step_executes_the_given_number_of_instructions() {
+        let (vc, _) = debug(&COUNTER, "step 4\nquit\n", 1);
+
+        assert_eq!(vc.variable_registers()[0], 2);
+        assert_eq!(vc.variable_registers()[1], 2);
+    }
+
+    #[test]
+    fn continue_stops_at_breakpoint() {
+        let (vc, output) = debug(&COUNTER, "b 202\nc\nc\nquit\n", 1);
+
+        assert_eq!(vc.program_counter(), 0x202);
+        assert_eq!(vc.variable_registers()[0], 2);
+        assert!(output.contains("Breakpoint at 0x202"));
+    }
+
+    #[test]
+    fn skip_moves_past_a_faulting_instruction() {
+        // RET with an empty stack, then V0 = 0x42
+        let rom = [0x00, 0xEE, 0x60, 0x42];
+        let (vc, output) = debug(&rom, "c\nskip\ns\nq\n", 1);
+
+        assert!(output.contains("Returned from a subroutine with an empty stack at 0x200"));
+        assert_eq!(vc.variable_registers()[0], 0x42);
+    }
+
+    #[test]
+    fn watchpoint_reports_register_changes() {
+        let (vc, output) = debug(&COUNTER, "watch v1\nc\nquit\n", 1);
+
+        assert_eq!(vc.program_counter(), 0x204);
+        assert!(output.contains("Watchpoint V1: 0x0 -> 0x2 at 0x202"));
+    }
+
+    #[test]
+    fn timers_tick_once_per_frame_even_when_paused() {
+        // DT = V0 (after V0 = 3), then spin
+        let rom = [0x60, 0x03, 0xF0, 0x15, 0x12, 0x04];
+        let (vc, _) = debug(&rom, "step 5\nc\n", 2);
+
+        assert_eq!(vc.delay_timer(), 1);
+    }
+
+    #[test]
+    fn regs_and_hex_dump_print_state() {
+        let (_, output) = debug(&COUNTER, "s 2\nregs\nx 200 6\nq\n", 1);
+
+        assert!(output.contains("V0=01 V1=02 V2=00"));
+        assert!(output.contains("PC=204 I=000 DT=00 ST=00"));
+        assert!(output.contains("0200: 70 01 71 02 12 00"));
+    }
+
+    #[test]
+    fn hex_dump_stops_at_the_end_of_memory() {
+        let (_, output) = debug(&COUNTER, "x FF0 18446744073709551615\nq\n", 1);
+
+        assert!(output.contains("0FF0: 00"));
+        assert!(!output.contains("1000:"));
+    }
+
+    #[test]
+    fn watch_parses_targets() {
+        assert_eq!(Watchpoint::parse("vA").unwrap(), Watchpoint::Register(10));
+        assert_eq!(Watchpoint::parse("I").unwrap(), Watchpoint::Index);
+        assert_eq!(
+            Watchpoint::parse("0x3F0").unwrap(),
+            Watchpoint::Memory(0x3F0)
+        );
+        assert!(Watchpoint::parse("v10").is_err());
+    }
+}
@@ -0,0 +1,577 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use clap::ValueEnum;
+
+use crate::{
+    constants::ROM_START_ADDRESS,
+    instruction_parser::{parse_instruction, InstructionType},
+};
+
+/// Which assembly language to print instructions in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Syntax {
+    /// The mnemonics from Cowgod's Chip-8 Technical Reference, e.g. `LD V3, 0x21`
+    Cowgod,
+
+    /// The statements of the Octo assembler, e.g. `v3 := 0x21`
+    Octo,
+}
+
+/// What a run of bytes in the ROM turned out to be.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LineKind {
+    Instruction(String),
+    /// Bytes that don't decode to any instruction, likely sprites or other data
+    Data,
+    /// A byte that code never reaches or that `DXYN` draws, shown with its pixels
+    Sprite,
+}
+
+/// One line of a disassembly listing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    pub kind: LineKind,
+    /// The name other lines use to refer to this one, if anything refers to it
+    pub label: Option<String>,
+    syntax: Syntax,
+}
+
+impl Line {
+    /// The assembly for this line, without its address, raw bytes, or label.
+    pub fn statement(&self) -> String {
+        match &self.kind {
+            LineKind::Instruction(text) => text.clone(),
+            LineKind::Sprite => {
+                let pixels: String = (0..8)
+                    .rev()
+                    .map(|bit| {
+                        if self.bytes[0] >> bit & 1 == 1 {
+                            '#'
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect();
+                match self.syntax {
+                    Syntax::Cowgod => format!("DB {:#04X}  ; {}", self.bytes[0], pixels),
+                    Syntax::Octo => format!("{:#04X}  # {}", self.bytes[0], pixels),
+                }
+            }
+            LineKind::Data => {
+                let bytes: Vec<String> = self.bytes.iter().map(|b| format!("{:#04X}", b)).collect();
+                match self.syntax {
+                    Syntax::Cowgod => format!("DB {}", bytes.join(", ")),
+                    Syntax::Octo => bytes.join(" "),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let raw: String = self
+            .bytes
+            .chunks(2)
+            .map(|word| {
+                word.iter()
+                    .map(|b| format!("{:02X}", b))
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        if let Some(label) = &self.label {
+            match self.syntax {
+                Syntax::Cowgod => writeln!(f, "{}:", label)?,
+                Syntax::Octo => writeln!(f, ": {}", label)?,
+            }
+        }
+
+        write!(f, "{:04X}  {:<9}  {}", self.address, raw, self.statement())
+    }
+}
+
+/// Decodes a ROM from start to finish, two bytes at a time, as if it were loaded at `0x200`.
+/// This can't tell code from data, so sprites that happen to look like instructions are shown as
+/// instructions.
+pub fn disassemble(rom: &[u8], syntax: Syntax) -> Vec<Line> {
+    let mut lines = vec![];
+    let mut offset = 0;
+
+    while offset < rom.len() {
+        let address = ROM_START_ADDRESS + offset as u16;
+        let Some(&[high, low]) = rom.get(offset..offset + 2) else {
+            // An odd byte left at the end can only be data
+            lines.push(data_line(address, &rom[offset..], syntax));
+            break;
+        };
+
+        let word = u16::from_be_bytes([high, low]);
+        let line = match parse_instruction(word) {
+            // The address for `i := long` is the following word
+            Some(InstructionType::SetIndexRegisterLong) if offset + 4 <= rom.len() => {
+                let long_address = u16::from_be_bytes([rom[offset + 2], rom[offset + 3]]);
+                Line {
+                    address,
+                    bytes: rom[offset..offset + 4].to_vec(),
+                    kind: LineKind::Instruction(long_index_mnemonic(
+                        &format!("{:#06X}", long_address),
+                        syntax,
+                    )),
+                    label: None,
+                    syntax,
+                }
+            }
+            Some(InstructionType::SetIndexRegisterLong) | None => {
+                data_line(address, &rom[offset..offset + 2], syntax)
+            }
+            Some(instr) => Line {
+                address,
+                bytes: vec![high, low],
+                kind: LineKind::Instruction(mnemonic(&instr, syntax)),
+                label: None,
+                syntax,
+            },
+        };
+
+        offset += line.bytes.len();
+        lines.push(line);
+    }
+
+    lines
+}
+
+/// Decodes a ROM by following its control flow from `0x200`: jumps, calls, skips, and returns.
+/// Only bytes that execution can reach are shown as instructions, so code at odd addresses lines
+/// up and sprites in between routines aren't misread. Everything else is shown as sprite data,
+/// one byte per line. Branch targets, subroutines, and the sprites that `DXYN` draws are
+/// labelled. Computed jumps (`BNNN`) are only followed to their base address, so jump tables
+/// beyond the first entry show up as data.
+pub fn disassemble_recursive(rom: &[u8], syntax: Syntax) -> Vec<Line> {
+    let flow = ControlFlow::trace(rom);
+
+    // Lay out the listing first, so that labels are only given to addresses that start a line
+    let mut layout = vec![];
+    let mut offset = 0;
+    while offset < rom.len() {
+        let len = flow.instructions.get(&offset).copied().unwrap_or(1);
+        layout.push((offset, len.min(rom.len() - offset)));
+        offset += len;
+    }
+
+    let line_starts: BTreeSet<u16> = layout
+        .iter()
+        .map(|&(offset, _)| ROM_START_ADDRESS + offset as u16)
+        .collect();
+    let labels: BTreeMap<u16, String> = flow
+        .labels()
+        .into_iter()
+        .filter(|(address, _)| line_starts.contains(address))
+        .collect();
+    let address_text = |address: u16, width: usize| {
+        labels
+            .get(&address)
+            .cloned()
+            .unwrap_or_else(|| format!("{:#0width$X}", address, width = width))
+    };
+
+    layout
+        .into_iter()
+        .map(|(offset, len)| {
+            let address = ROM_START_ADDRESS + offset as u16;
+            let bytes = rom[offset..offset + len].to_vec();
+            let kind = if flow.instructions.contains_key(&offset) {
+                let word = u16::from_be_bytes([bytes[0], bytes[1]]);
+                LineKind::Instruction(match parse_instruction(word) {
+                    Some(InstructionType::SetIndexRegisterLong) => long_index_mnemonic(
+                        &address_text(u16::from_be_bytes([bytes[2], bytes[3]]), 6),
+                        syntax,
+                    ),
+                    Some(instr) => mnemonic_with_addresses(&instr, syntax, &|a| address_text(a, 5)),
+                    None => unreachable!("only decodable words are traced"),
+                })
+            } else {
+                LineKind::Sprite
+            };
+
+            Line {
+                address,
+                bytes,
+                kind,
+                label: labels.get(&address).cloned(),
+                syntax,
+            }
+        })
+        .collect()
+}
+
+/// What tracing a ROM's control flow found out about it.
+#[derive(Default)]
+struct ControlFlow {
+    /// The offsets into the ROM of every reachable instruction, and how many bytes each one takes
+    instructions: BTreeMap<usize, usize>,
+    jump_targets: BTreeSet<u16>,
+    subroutines: BTreeSet<u16>,
+    sprites: BTreeSet<u16>,
+}
+
+impl ControlFlow {
+    fn trace(rom: &[u8]) -> Self {
+        let mut flow = Self::default();
+
+        // Each entry is where a path starts, and what I is known to hold there
+        let mut queue = vec![(ROM_START_ADDRESS, None)];
+        while let Some((mut address, mut index)) = queue.pop() {
+            while let Some((instr, len)) = decode_at(rom, address) {
+                let offset = (address - ROM_START_ADDRESS) as usize;
+                if flow.instructions.insert(offset, len).is_some() {
+                    break;
+                }
+                let next = address.wrapping_add(len as u16);
+
+                match instr {
+                    InstructionType::JumpToMemoryLocation(target)
+                    | InstructionType::JumpWithOffset(target) => {
+                        flow.jump_targets.insert(target);
+                        queue.push((target, index));
+                        break;
+                    }
+                    InstructionType::CallSubroutine(target) => {
+                        flow.subroutines.insert(target);
+                        queue.push((target, None));
+                        // The subroutine could have changed I
+                        index = None;
+                    }
+                    InstructionType::ReturnFromSubroutine | InstructionType::Exit => break,
+                    InstructionType::SkipIfRegisterEqValue { .. }
+                    | InstructionType::SkipIfRegisterNeqValue { .. }
+                    | InstructionType::SkipIfRegistersEq { .. }
+                    | InstructionType::SkipIfRegistersNeq { .. }
+                    | InstructionType::SkipIfPressedVX(_)
+                    | InstructionType::SkipIfNotPressedVX(_) => {
+                        let skipped_len = match decode_at(rom, next) {
+                            Some((_, len)) => len,
+                            None => 2,
+                        };
+                        queue.push((next.wrapping_add(skipped_len as u16), index));
+                    }
+                    InstructionType::SetIndexRegister(target) => index = Some(target),
+                    InstructionType::SetIndexRegisterLong => {
+                        index = Some(u16::from_be_bytes([rom[offset + 2], rom[offset + 3]]))
+                    }
+                    InstructionType::AddToIndexFromVX(_)
+                    | InstructionType::SetIndexToFontCharInVX(_)
+                    | InstructionType::SetIndexToBigFontCharInVX(_)
+                    | InstructionType::StoreVariableRegistersToMemoryUpToVX(_)
+                    | InstructionType::LoadMemoryToVariableRegistersFromVXAddress(_) => {
+                        index = None
+                    }
+                    InstructionType::Display { .. } => {
+                        if let Some(sprite) = index {
+                            flow.sprites.insert(sprite);
+                        }
+                    }
+                    _ => {}
+                }
+
+                address = next;
+            }
+        }
+
+        flow
+    }
+
+    /// Names for every address something refers to. Subroutines take priority over plain jump
+    /// targets, and code over sprites.
+    fn labels(&self) -> BTreeMap<u16, String> {
+        let mut labels = BTreeMap::new();
+        for &address in &self.sprites {
+            labels.insert(address, format!("sprite_{:03X}", address));
+        }
+        for &address in &self.jump_targets {
+            labels.insert(address, format!("label_{:03X}", address));
+        }
+        for &address in &self.subroutines {
+            labels.insert(address, format!("sub_{:03X}", address));
+        }
+        labels
+    }
+}
+
+/// Decodes the instruction at `address`, and how many bytes it takes up, if it is inside the ROM.
+fn decode_at(rom: &[u8], address: u16) -> Option<(InstructionType, usize)> {
+    let offset = address.checked_sub(ROM_START_ADDRESS)? as usize;
+    let &[high, low] = rom.get(offset..offset + 2)? else {
+        return None;
+    };
+
+    match parse_instruction(u16::from_be_bytes([high, low]))? {
+        InstructionType::SetIndexRegisterLong if offset + 4 > rom.len() => None,
+        InstructionType::SetIndexRegisterLong => Some((InstructionType::SetIndexRegisterLong, 4)),
+        instr => Some((instr, 2)),
+    }
+}
+
+fn data_line(address: u16, bytes: &[u8], syntax: Syntax) -> Line {
+    Line {
+        address,
+        bytes: bytes.to_vec(),
+        kind: LineKind::Data,
+        label: None,
+        syntax,
+    }
+}
+
+/// Writes a single instruction in the given syntax. `SetIndexRegisterLong` needs the word after
+/// it, which `disassemble` fills in, so here it is written without an address.
+pub fn mnemonic(instr: &InstructionType, syntax: Syntax) -> String {
+    mnemonic_with_addresses(instr, syntax, &|address| format!("{:#05X}", address))
+}
+
+/// Like `mnemonic`, but with the addresses of jumps, calls, and loads into I written by `address`.
+fn mnemonic_with_addresses(
+    instr: &InstructionType,
+    syntax: Syntax,
+    address: &dyn Fn(u16) -> String,
+) -> String {
+    match syntax {
+        Syntax::Cowgod => cowgod(instr, address),
+        Syntax::Octo => octo(instr, address),
+    }
+}
+
+fn long_index_mnemonic(address: &str, syntax: Syntax) -> String {
+    match syntax {
+        Syntax::Cowgod => format!("LD I, LONG {}", address),
+        Syntax::Octo => format!("i := long {}", address),
+    }
+}
+
+fn cowgod(instr: &InstructionType, address: &dyn Fn(u16) -> String) -> String {
+    use InstructionType::*;
+
+    match *instr {
+        ClearScreen => "CLS".into(),
+        JumpToMemoryLocation(nnn) => format!("JP {}", address(nnn)),
+        CallSubroutine(nnn) => format!("CALL {}", address(nnn)),
+        ReturnFromSubroutine => "RET".into(),
+        SkipIfRegisterEqValue { vx, value } => format!("SE V{:X}, {:#04X}", vx, value),
+        SkipIfRegisterNeqValue { vx, value } => format!("SNE V{:X}, {:#04X}", vx, value),
+        SkipIfRegistersEq { vx, vy } => format!("SE V{:X}, V{:X}", vx, vy),
+        SkipIfRegistersNeq { vx, vy } => format!("SNE V{:X}, V{:X}", vx, vy),
+        UpdateRegister { vx, value } => format!("LD V{:X}, {:#04X}", vx, value),
+        AddValueToRegister { vx, value } => format!("ADD V{:X}, {:#04X}", vx, value),
+        CopyRegister { vx, vy } => format!("LD V{:X}, V{:X}", vx, vy),
+        BitwiseOR { vx, vy } => format!("OR V{:X}, V{:X}", vx, vy),
+        BitwiseAND { vx, vy } => format!("AND V{:X}, V{:X}", vx, vy),
+        BitwiseXOR { vx, vy } => format!("XOR V{:X}, V{:X}", vx, vy),
+        AddRegisterToRegister { vx, vy } => format!("ADD V{:X}, V{:X}", vx, vy),
+        SubtractXY { vx, vy } => format!("SUB V{:X}, V{:X}", vx, vy),
+        SubtractYX { vx, vy } => format!("SUBN V{:X}, V{:X}", vx, vy),
+        ShiftRight { vx, vy } => format!("SHR V{:X}, V{:X}", vx, vy),
+        ShiftLeft { vx, vy } => format!("SHL V{:X}, V{:X}", vx, vy),
+        SetIndexRegister(nnn) => format!("LD I, {}", address(nnn)),
+        JumpWithOffset(nnn) => format!("JP V0, {}", address(nnn)),
+        GenerateRandomNumber { vx, bitmask } => format!("RND V{:X}, {:#04X}", vx, bitmask),
+        Display { vx, vy, n } => format!("DRW V{:X}, V{:X}, {}", vx, vy, n),
+        SkipIfPressedVX(x) => format!("SKP V{:X}", x),
+        SkipIfNotPressedVX(x) => format!("SKNP V{:X}", x),
+        FetchDelayTimerToVX(x) => format!("LD V{:X}, DT", x),
+        SetDelayTimerToVX(x) => format!("LD DT, V{:X}", x),
+        SetSoundTimerToVX(x) => format!("LD ST, V{:X}", x),
+        AddToIndexFromVX(x) => format!("ADD I, V{:X}", x),
+        WaitForKeyInVX(x) => format!("LD V{:X}, K", x),
+        SetIndexToFontCharInVX(x) => format!("LD F, V{:X}", x),
+        BinaryCodedDecimalConversionForVX(x) => format!("LD B, V{:X}", x),
+        StoreVariableRegistersToMemoryUpToVX(x) => format!("LD [I], V{:X}", x),
+        LoadMemoryToVariableRegistersFromVXAddress(x) => format!("LD V{:X}, [I]", x),
+        ScrollDown(n) => format!("SCD {}", n),
+        ScrollRight => "SCR".into(),
+        ScrollLeft => "SCL".into(),
+        Exit => "EXIT".into(),
+        LowResolution => "LOW".into(),
+        HighResolution => "HIGH".into(),
+        SetIndexToBigFontCharInVX(x) => format!("LD HF, V{:X}", x),
+        StoreVariableRegistersToFlagsUpToVX(x) => format!("LD R, V{:X}", x),
+        LoadFlagsToVariableRegistersUpToVX(x) => format!("LD V{:X}, R", x),
+        StoreRegisterRange { vx, vy } => format!("SAVE V{:X}, V{:X}", vx, vy),
+        LoadRegisterRange { vx, vy } => format!("LOAD V{:X}, V{:X}", vx, vy),
+        SetIndexRegisterLong => "LD I, LONG".into(),
+        SelectPlanes(n) => format!("PLANE {}", n),
+        LoadAudioPattern => "AUDIO".into(),
+        SetPitchToVX(x) => format!("PITCH V{:X}", x),
+        ScrollUp(n) => format!("SCU {}", n),
+    }
+}
+
+/// Octo only has conditional blocks, so skips are written as the condition under which the next
+/// instruction runs, which is the opposite of the skip's own condition.
+fn octo(instr: &InstructionType, address: &dyn Fn(u16) -> String) -> String {
+    use InstructionType::*;
+
+    match *instr {
+        ClearScreen => "clear".into(),
+        JumpToMemoryLocation(nnn) => format!("jump {}", address(nnn)),
+        CallSubroutine(nnn) => format!(":call {}", address(nnn)),
+        ReturnFromSubroutine => "return".into(),
+        SkipIfRegisterEqValue { vx, value } => format!("if v{:x} != {:#04X} then", vx, value),
+        SkipIfRegisterNeqValue { vx, value } => format!("if v{:x} == {:#04X} then", vx, value),
+        SkipIfRegistersEq { vx, vy } => format!("if v{:x} != v{:x} then", vx, vy),
+        SkipIfRegistersNeq { vx, vy } => format!("if v{:x} == v{:x} then", vx, vy),
+        UpdateRegister { vx, value } => format!("v{:x} := {:#04X}", vx, value),
+        AddValueToRegister { vx, value } => format!("v{:x} += {:#04X}", vx, value),
+        CopyRegister { vx, vy } => format!("v{:x} := v{:x}", vx, vy),
+        BitwiseOR { vx, vy } => format!("v{:x} |= v{:x}", vx, vy),
+        BitwiseAND { vx, vy } => format!("v{:x} &= v{:x}", vx, vy),
+        BitwiseXOR { vx, vy } => format!("v{:x} ^= v{:x}", vx, vy),
+        AddRegisterToRegister { vx, vy } => format!("v{:x} += v{:x}", vx, vy),
+        SubtractXY { vx, vy } => format!("v{:x} -= v{:x}", vx, vy),
+        SubtractYX { vx, vy } => format!("v{:x} =- v{:x}", vx, vy),
+        ShiftRight { vx, vy } => format!("v{:x} >>= v{:x}", vx, vy),
+        ShiftLeft { vx, vy } => format!("v{:x} <<= v{:x}", vx, vy),
+        SetIndexRegister(nnn) => format!("i := {}", address(nnn)),
+        JumpWithOffset(nnn) => format!("jump0 {}", address(nnn)),
+        GenerateRandomNumber { vx, bitmask } => format!("v{:x} := random {:#04X}", vx, bitmask),
+        Display { vx, vy, n } => format!("sprite v{:x} v{:x} {}", vx, vy, n),
+        SkipIfPressedVX(x) => format!("if v{:x} -key then", x),
+        SkipIfNotPressedVX(x) => format!("if v{:x} key then", x),
+        FetchDelayTimerToVX(x) => format!("v{:x} := delay", x),
+        SetDelayTimerToVX(x) => format!("delay := v{:x}", x),
+        SetSoundTimerToVX(x) => format!("buzzer := v{:x}", x),
+        AddToIndexFromVX(x) => format!("i += v{:x}", x),
+        WaitForKeyInVX(x) => format!("v{:x} := key", x),
+        SetIndexToFontCharInVX(x) => format!("i := hex v{:x}", x),
+        BinaryCodedDecimalConversionForVX(x) => format!("bcd v{:x}", x),
+        StoreVariableRegistersToMemoryUpToVX(x) => format!("save v{:x}", x),
+        LoadMemoryToVariableRegistersFromVXAddress(x) => format!("load v{:x}", x),
+        ScrollDown(n) => format!("scroll-down {}", n),
+        ScrollRight => "scroll-right".into(),
+        ScrollLeft => "scroll-left".into(),
+        Exit => "exit".into(),
+        LowResolution => "lores".into(),
+        HighResolution => "hires".into(),
+        SetIndexToBigFontCharInVX(x) => format!("i := bighex v{:x}", x),
+        StoreVariableRegistersToFlagsUpToVX(x) => format!("saveflags v{:x}", x),
+        LoadFlagsToVariableRegistersUpToVX(x) => format!("loadflags v{:x}", x),
+        StoreRegisterRange { vx, vy } => format!("save v{:x} - v{:x}", vx, vy),
+        LoadRegisterRange { vx, vy } => format!("load v{:x} - v{:x}", vx, vy),
+        SetIndexRegisterLong => "i := long".into(),
+        SelectPlanes(n) => format!("plane {}", n),
+        LoadAudioPattern => "audio".into(),
+        SetPitchToVX(x) => format!("pitch := v{:x}", x),
+        ScrollUp(n) => format!("scroll-up {}", n),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(0x6321, "LD V3, 0x21", "v3 := 0x21")]
+    #[case(0xD59A, "DRW V5, V9, 10", "sprite v5 v9 10")]
+    #[case(0x3A05, "SE VA, 0x05", "if va != 0x05 then")]
+    #[case(0x1234, "JP 0x234", "jump 0x234")]
+    #[case(0x8AB7, "SUBN VA, VB", "va =- vb")]
+    #[case(0xF165, "LD V1, [I]", "load v1")]
+    #[case(0x5232, "SAVE V2, V3", "save v2 - v3")]
+    fn mnemonics_in_both_syntaxes(#[case] word: u16, #[case] cowgod: &str, #[case] octo: &str) {
+        let instr = parse_instruction(word).unwrap();
+
+        assert_eq!(mnemonic(&instr, Syntax::Cowgod), cowgod);
+        assert_eq!(mnemonic(&instr, Syntax::Octo), octo);
+    }
+
+    fn listing(lines: Vec<Line>) -> String {
+        lines
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn recursive_listing_separates_code_from_sprites() {
+        let rom = [
+            0x22, 0x06, // call the subroutine
+            0x12, 0x02, // loop forever
+            0xFF, 0xFF, // never reached
+            0xA2, 0x0C, // point I at the sprite
+            0xD0, 0x12, // draw it
+            0x00, 0xEE, // return
+            0x3C, 0x7E, // the sprite
+        ];
+
+        assert_eq!(
+            listing(disassemble_recursive(&rom, Syntax::Cowgod)),
+            [
+                "0200  2206       CALL sub_206",
+                "label_202:",
+                "0202  1202       JP label_202",
+                "0204  FF         DB 0xFF  ; ########",
+                "0205  FF         DB 0xFF  ; ########",
+                "sub_206:",
+                "0206  A20C       LD I, sprite_20C",
+                "0208  D012       DRW V0, V1, 2",
+                "020A  00EE       RET",
+                "sprite_20C:",
+                "020C  3C         DB 0x3C  ; ..####..",
+                "020D  7E         DB 0x7E  ; .######.",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn recursive_listing_follows_skips_and_odd_addresses() {
+        let rom = [
+            0x12, 0x03, // jump over a padding byte
+            0x00, // padding
+            0x30, 0x00, // skip the exit if V0 is 0
+            0x00, 0xFD, // exit
+            0x00, 0xE0, // clear
+            0x00, 0xFD, // exit
+        ];
+
+        assert_eq!(
+            listing(disassemble_recursive(&rom, Syntax::Octo)),
+            [
+                "0200  1203       jump label_203",
+                "0202  00         0x00  # ........",
+                ": label_203",
+                "0203  3000       if v0 != 0x00 then",
+                "0205  00FD       exit",
+                "0207  00E0       clear",
+                "0209  00FD       exit",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn listing_marks_undecodable_bytes_as_data() {
+        let rom = [0x00, 0xE0, 0xFF, 0xFF, 0xF0, 0x00, 0x12, 0x34, 0x80];
+        let lines: Vec<String> = disassemble(&rom, Syntax::Cowgod)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+
+        assert_eq!(
+            lines,
+            [
+                "0200  00E0       CLS",
+                "0202  FFFF       DB 0xFF, 0xFF",
+                "0204  F000 1234  LD I, LONG 0x1234",
+                "0208  80         DB 0x80",
+            ]
+        );
+    }
+}
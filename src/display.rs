@@ -0,0 +1,202 @@
+use std::ops::Index;
+
+use crate::constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH, HIRES_DISPLAY_HEIGHT, HIRES_DISPLAY_WIDTH};
+
+/// The bitplane that plain CHIP-8 and SUPER-CHIP programs draw to.
+pub const DEFAULT_PLANES: u8 = 0b01;
+
+/// A framebuffer with two bitplanes whose resolution can change at runtime, indexed as
+/// `display[y][x]`. Each pixel is the set of planes lit at that position (`0..=3`), which
+/// frontends use as an index into their palette. Programs that never select a plane only ever
+/// touch the first one, so for them every pixel is either 0 or 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Display {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Display {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width * height],
+        }
+    }
+
+    /// Rebuilds a display from its pixels in row order, as long as there are exactly
+    /// `width * height` of them.
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<u8>) -> Option<Self> {
+        (width > 0 && pixels.len() == width * height).then_some(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Whether the display is in SUPER-CHIP's 128x64 high resolution mode.
+    pub fn is_high_resolution(&self) -> bool {
+        self.width == HIRES_DISPLAY_WIDTH as usize
+    }
+
+    /// Switches between 64x32 and 128x64, clearing the screen.
+    pub fn set_high_resolution(&mut self, high_resolution: bool) {
+        *self = if high_resolution {
+            Display::new(HIRES_DISPLAY_WIDTH as usize, HIRES_DISPLAY_HEIGHT as usize)
+        } else {
+            Display::default()
+        };
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        self.pixels.chunks(self.width)
+    }
+
+    /// Turns off every pixel in the given planes.
+    pub fn clear(&mut self, planes: u8) {
+        for pixel in &mut self.pixels {
+            *pixel &= !planes;
+        }
+    }
+
+    /// Flips the pixel at (`x`, `y`) in a single plane, returning whether it was turned off.
+    pub fn toggle(&mut self, x: usize, y: usize, plane: u8) -> bool {
+        let pixel = &mut self.pixels[y * self.width + x];
+        *pixel ^= plane;
+        *pixel & plane == 0
+    }
+
+    /// Moves the given planes up by `n` pixels, filling the bottom with blank rows.
+    pub fn scroll_up(&mut self, n: usize, planes: u8) {
+        self.scroll(0, -(n as isize), planes);
+    }
+
+    /// Moves the given planes down by `n` pixels, filling the top with blank rows.
+    pub fn scroll_down(&mut self, n: usize, planes: u8) {
+        self.scroll(0, n as isize, planes);
+    }
+
+    /// Moves the given planes right by `n` pixels, filling the left edge with blank columns.
+    pub fn scroll_right(&mut self, n: usize, planes: u8) {
+        self.scroll(n as isize, 0, planes);
+    }
+
+    /// Moves the given planes left by `n` pixels, filling the right edge with blank columns.
+    pub fn scroll_left(&mut self, n: usize, planes: u8) {
+        self.scroll(-(n as isize), 0, planes);
+    }
+
+    fn scroll(&mut self, dx: isize, dy: isize, planes: u8) {
+        let source = self.pixels.clone();
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let source_x = x as isize - dx;
+                let source_y = y as isize - dy;
+                let in_bounds = (0..self.width as isize).contains(&source_x)
+                    && (0..self.height as isize).contains(&source_y);
+
+                let moved = if in_bounds {
+                    source[source_y as usize * self.width + source_x as usize] & planes
+                } else {
+                    0
+                };
+
+                let pixel = &mut self.pixels[y * self.width + x];
+                *pixel = (*pixel & !planes) | moved;
+            }
+        }
+    }
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Display::new(DISPLAY_WIDTH as usize, DISPLAY_HEIGHT as usize)
+    }
+}
+
+impl Index<usize> for Display {
+    type Output = [u8];
+
+    fn index(&self, y: usize) -> &[u8] {
+        &self.pixels[y * self.width..(y + 1) * self.width]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// Builds a display from rows of plane numbers, with `.` for blank pixels.
+    fn display_from_rows(rows: &[&str]) -> Display {
+        let mut display = Display::new(rows[0].len(), rows.len());
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                let planes = c.to_digit(4).unwrap_or(0) as u8;
+                for plane in [0b01, 0b10] {
+                    if planes & plane != 0 {
+                        display.toggle(x, y, plane);
+                    }
+                }
+            }
+        }
+        display
+    }
+
+    #[test]
+    fn scroll_down_shifts_rows_and_blanks_the_top() {
+        let mut display = display_from_rows(&["1..", ".1.", "..1"]);
+        display.scroll_down(1, DEFAULT_PLANES);
+
+        assert_eq!(display, display_from_rows(&["...", "1..", ".1."]));
+    }
+
+    #[test]
+    fn scroll_right_and_left_blank_the_exposed_edge() {
+        let mut display = display_from_rows(&["11.", ".11"]);
+        display.scroll_right(1, DEFAULT_PLANES);
+        assert_eq!(display, display_from_rows(&[".11", "..1"]));
+
+        display.scroll_left(2, DEFAULT_PLANES);
+        assert_eq!(display, display_from_rows(&["1..", "1.."]));
+    }
+
+    #[test]
+    fn scroll_only_moves_selected_planes() {
+        let mut display = display_from_rows(&["3.", ".."]);
+        display.scroll_up(1, 0b10);
+        display.scroll_down(1, 0b10);
+
+        assert_eq!(display, display_from_rows(&["1.", ".."]));
+    }
+
+    #[test]
+    fn clear_only_affects_selected_planes() {
+        let mut display = display_from_rows(&["3.", "21"]);
+        display.clear(0b01);
+
+        assert_eq!(display, display_from_rows(&["2.", "2."]));
+    }
+
+    #[test]
+    fn toggle_reports_pixels_turned_off() {
+        let mut display = Display::default();
+
+        assert!(!display.toggle(3, 4, 0b01));
+        assert_eq!(display[4][3], 1);
+        assert!(!display.toggle(3, 4, 0b10));
+        assert_eq!(display[4][3], 3);
+        assert!(display.toggle(3, 4, 0b01));
+        assert_eq!(display[4][3], 2);
+    }
+}
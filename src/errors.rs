@@ -0,0 +1,74 @@
+use std::fmt;
+
+use clap::ValueEnum;
+
+/// A fault raised by the virtual computer. The emulator never prints or aborts on its own; these
+/// are handed back to whoever is driving it to decide what to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip8Error {
+    /// The word at `address` doesn't decode to any instruction the profile has
+    UnknownOpcode { opcode: u16, address: u16 },
+
+    /// 00EE at `address` ran with nothing on the stack to return to
+    StackUnderflow { address: u16 },
+
+    /// 2NNN at `address` called a subroutine with the stack already full
+    StackOverflow { address: u16 },
+
+    /// An instruction or fetch tried to touch memory past the end of RAM
+    MemoryOutOfBounds { address: usize },
+
+    /// EX9E or EXA1 at `address` asked about a key that isn't on the keypad
+    InvalidKey { key: u8, address: u16 },
+
+    /// The ROM doesn't fit between `0x200` and the end of memory
+    RomTooLarge { size: usize, max_size: usize },
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Chip8Error::UnknownOpcode { opcode, address } => {
+                write!(f, "Unknown opcode {:04X} at {:#05X}", opcode, address)
+            }
+            Chip8Error::StackUnderflow { address } => write!(
+                f,
+                "Returned from a subroutine with an empty stack at {:#05X}",
+                address
+            ),
+            Chip8Error::StackOverflow { address } => write!(
+                f,
+                "Called a subroutine with a full stack at {:#05X}",
+                address
+            ),
+            Chip8Error::MemoryOutOfBounds { address } => {
+                write!(f, "Memory address {:#X} is out of bounds", address)
+            }
+            Chip8Error::InvalidKey { key, address } => write!(
+                f,
+                "Key {} is out of the range [0, 15] at {:#05X}",
+                key, address
+            ),
+            Chip8Error::RomTooLarge { size, max_size } => write!(
+                f,
+                "ROM is {} bytes, but at most {} bytes fit in memory",
+                size, max_size
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Chip8Error {}
+
+/// What the emulator loop does when the program faults.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum FaultPolicy {
+    /// Stop the emulator and report the fault
+    Halt,
+
+    /// Pause in the debugger at the faulting instruction
+    Debug,
+
+    /// Skip the faulting instruction and carry on
+    Ignore,
+}
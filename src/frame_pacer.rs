@@ -0,0 +1,63 @@
+use std::time::{Duration, Instant};
+
+/// How often the timers tick and the screen is presented.
+pub const FRAMES_PER_SECOND: u32 = 60;
+
+/// If the host falls this many frames behind, give up on catching up rather than running a burst
+/// of frames as fast as possible.
+const MAX_FRAMES_BEHIND: u32 = 5;
+
+/// Keeps the emulator loop running at `FRAMES_PER_SECOND` in real time. Deadlines are
+/// accumulated from the start rather than measured from the end of each frame, so small sleep
+/// overshoots don't add up into drift.
+pub struct FramePacer {
+    frame_duration: Duration,
+    next_frame: Instant,
+}
+
+impl FramePacer {
+    pub fn new() -> Self {
+        Self {
+            frame_duration: Duration::from_secs(1) / FRAMES_PER_SECOND,
+            next_frame: Instant::now(),
+        }
+    }
+
+    /// Sleeps until it is time to start the next frame.
+    pub fn wait_for_next_frame(&mut self) {
+        self.next_frame += self.frame_duration;
+
+        let now = Instant::now();
+        if self.next_frame > now {
+            std::thread::sleep(self.next_frame - now);
+        } else if now - self.next_frame > self.frame_duration * MAX_FRAMES_BEHIND {
+            self.next_frame = now;
+        }
+    }
+}
+
+impl Default for FramePacer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Converts a clock speed into a whole number of instructions per frame, running at least one.
+pub fn instructions_per_frame_from_hz(cpu_hz: u32) -> u32 {
+    ((cpu_hz as f64 / FRAMES_PER_SECOND as f64).round() as u32).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(600, 10)]
+    #[case(700, 12)]
+    #[case(1, 1)]
+    fn instructions_per_frame_from_hz_rounds(#[case] cpu_hz: u32, #[case] expected: u32) {
+        assert_eq!(instructions_per_frame_from_hz(cpu_hz), expected);
+    }
+}
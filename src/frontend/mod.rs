@@ -0,0 +1,92 @@
+mod null;
+mod sdl;
+mod terminal;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use sdl2::Sdl;
+
+use crate::{display::Display, keymap::Keymap, virtual_computer::KeyPress};
+
+pub use null::NullFrontend;
+pub use sdl::SdlFrontend;
+pub use terminal::TerminalFrontend;
+
+/// Something the user did that the emulator loop needs to react to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrontendEvent {
+    Quit,
+    KeyDown(KeyPress),
+    KeyUp(KeyPress),
+    /// Snapshot the emulator into the numbered slot
+    SaveState(u8),
+    /// Restore the emulator from the numbered slot
+    LoadState(u8),
+    /// Save what's on screen as an image
+    Screenshot,
+    /// Start recording every frame, or stop the recording that's running
+    ToggleRecording,
+    /// Start stepping back through recent frames, until `StopRewinding`
+    StartRewinding,
+    StopRewinding,
+}
+
+/// A place to show the framebuffer and collect input from. The emulator loop hands every frontend
+/// the same framebuffer, so the core never needs to know how (or whether) it is being drawn.
+pub trait Frontend {
+    /// Returns all of the input events that arrived since the last call, without blocking.
+    fn poll_events(&mut self) -> Vec<FrontendEvent>;
+
+    /// Presents the current contents of the framebuffer.
+    fn render(&mut self, display: &Display) -> Result<()>;
+
+    /// Whether the emulator loop should wait between frames to run in real time. Frontends that
+    /// nobody is watching can return false to run as fast as possible.
+    fn paces_to_real_time(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Backend {
+    /// A desktop window drawn with SDL
+    Sdl,
+
+    /// Half-block characters drawn in the current terminal
+    Terminal,
+
+    /// No output at all, for headless runs
+    Null,
+}
+
+/// How the SDL window is sized, and how the framebuffer is fitted into it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowOptions {
+    /// Window pixels per low resolution CHIP-8 pixel when the window opens
+    pub scale: u32,
+    pub fullscreen: bool,
+    /// Keeps every CHIP-8 pixel the same whole number of window pixels across, at the cost of a
+    /// wider border. Otherwise the framebuffer fills as much of the window as its shape allows.
+    pub integer_scaling: bool,
+}
+
+impl Backend {
+    /// Opens the frontend. `sdl_context` must be present for the SDL backend, and `window` only
+    /// matters to it.
+    pub fn create(
+        self,
+        sdl_context: Option<&Sdl>,
+        keymap: Keymap,
+        window: WindowOptions,
+    ) -> Result<Box<dyn Frontend>> {
+        Ok(match self {
+            Backend::Sdl => Box::new(SdlFrontend::new(
+                sdl_context.expect("SDL is initialized for the SDL backend"),
+                keymap,
+                window,
+            )?),
+            Backend::Terminal => Box::new(TerminalFrontend::new(keymap)?),
+            Backend::Null => Box::new(NullFrontend),
+        })
+    }
+}
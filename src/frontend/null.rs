@@ -0,0 +1,21 @@
+use anyhow::Result;
+
+use super::{Frontend, FrontendEvent};
+use crate::display::Display;
+
+/// A frontend that draws nothing and never produces input.
+pub struct NullFrontend;
+
+impl Frontend for NullFrontend {
+    fn poll_events(&mut self) -> Vec<FrontendEvent> {
+        vec![]
+    }
+
+    fn render(&mut self, _display: &Display) -> Result<()> {
+        Ok(())
+    }
+
+    fn paces_to_real_time(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,338 @@
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::{anyhow, Result};
+use sdl2::{
+    controller::{Axis, GameController},
+    event::Event,
+    keyboard::{Keycode, Mod},
+    pixels::Color,
+    rect::Rect,
+    render::WindowCanvas,
+    EventPump, GameControllerSubsystem, Sdl,
+};
+
+use super::{Frontend, FrontendEvent, WindowOptions};
+use crate::{
+    constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH, PALETTE},
+    display::Display,
+    keymap::Keymap,
+};
+
+/// How far a stick or trigger has to be pushed, out of 32767, before it counts as pressed.
+const AXIS_DEAD_ZONE: i16 = 16_000;
+
+/// A desktop window, with input read from the keyboard and any connected game controllers.
+pub struct SdlFrontend {
+    canvas: WindowCanvas,
+    integer_scaling: bool,
+    event_pump: EventPump,
+    keymap: Keymap,
+    game_controller: GameControllerSubsystem,
+    /// Open controllers by instance id. SDL only sends events for controllers that stay open.
+    controllers: HashMap<u32, GameController>,
+    /// The gamepad inputs each controller is holding down, named as in the keymap
+    held_inputs: HashMap<u32, BTreeSet<String>>,
+}
+
+impl SdlFrontend {
+    pub fn new(sdl_context: &Sdl, keymap: Keymap, options: WindowOptions) -> Result<Self> {
+        let video_subsystem = sdl_context.video().map_err(|e| anyhow!(e))?;
+
+        let mut window = video_subsystem.window(
+            "chip8",
+            DISPLAY_WIDTH as u32 * options.scale,
+            DISPLAY_HEIGHT as u32 * options.scale,
+        );
+        window.position_centered().resizable();
+        if options.fullscreen {
+            window.fullscreen_desktop();
+        }
+        let window = window.build()?;
+
+        let mut canvas = window.into_canvas().build()?;
+
+        canvas.set_draw_color(Color::BLACK);
+        canvas.clear();
+        canvas.present();
+
+        let event_pump = sdl_context.event_pump().map_err(|e| anyhow!(e))?;
+        // Controllers that are already plugged in get a ControllerDeviceAdded event at startup,
+        // so they are opened the same way as ones plugged in later
+        let game_controller = sdl_context.game_controller().map_err(|e| anyhow!(e))?;
+
+        Ok(Self {
+            canvas,
+            integer_scaling: options.integer_scaling,
+            event_pump,
+            keymap,
+            game_controller,
+            controllers: HashMap::new(),
+            held_inputs: HashMap::new(),
+        })
+    }
+
+    fn keyboard_event(&self, event: Event) -> Option<FrontendEvent> {
+        match event {
+            Event::Quit { .. }
+            | Event::KeyDown {
+                keycode: Some(Keycode::Escape),
+                ..
+            } => Some(FrontendEvent::Quit),
+            Event::KeyDown {
+                keycode: Some(Keycode::F12),
+                repeat: false,
+                ..
+            } => Some(FrontendEvent::Screenshot),
+            Event::KeyDown {
+                keycode: Some(Keycode::F11),
+                repeat: false,
+                ..
+            } => Some(FrontendEvent::ToggleRecording),
+            // Held rather than pressed, so repeats are ignored and letting go matters
+            Event::KeyDown {
+                keycode: Some(Keycode::Backspace),
+                repeat,
+                ..
+            } => (!repeat).then_some(FrontendEvent::StartRewinding),
+            Event::KeyUp {
+                keycode: Some(Keycode::Backspace),
+                ..
+            } => Some(FrontendEvent::StopRewinding),
+            // F1-F9 load the numbered save state slot, and holding shift saves to it instead
+            Event::KeyDown {
+                keycode: Some(keycode),
+                keymod,
+                repeat: false,
+                ..
+            } if save_state_slot(keycode).is_some() => {
+                let slot = save_state_slot(keycode)?;
+                if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                    Some(FrontendEvent::SaveState(slot))
+                } else {
+                    Some(FrontendEvent::LoadState(slot))
+                }
+            }
+            // Key repeat would otherwise look like fresh presses to the program
+            Event::KeyDown {
+                keycode: Some(keycode),
+                repeat: false,
+                ..
+            } => self
+                .keymap
+                .key_for(&keycode.name())
+                .map(FrontendEvent::KeyDown),
+            Event::KeyUp {
+                keycode: Some(keycode),
+                ..
+            } => self
+                .keymap
+                .key_for(&keycode.name())
+                .map(FrontendEvent::KeyUp),
+            _ => None,
+        }
+    }
+
+    fn open_controller(&mut self, joystick_index: u32) {
+        match self.game_controller.open(joystick_index) {
+            Ok(controller) => {
+                self.controllers
+                    .insert(controller.instance_id(), controller);
+            }
+            Err(e) => eprintln!("Couldn't open game controller: {}", e),
+        }
+    }
+
+    /// Lets go of everything an unplugged controller was holding, so no keys get stuck down.
+    fn close_controller(&mut self, instance_id: u32, events: &mut Vec<FrontendEvent>) {
+        self.controllers.remove(&instance_id);
+        for input in self.held_inputs.remove(&instance_id).unwrap_or_default() {
+            events.extend(
+                self.keymap
+                    .key_for_gamepad(&input)
+                    .map(FrontendEvent::KeyUp),
+            );
+        }
+    }
+
+    fn press(&mut self, instance_id: u32, input: String, events: &mut Vec<FrontendEvent>) {
+        let key = self.keymap.key_for_gamepad(&input);
+        if self
+            .held_inputs
+            .entry(instance_id)
+            .or_default()
+            .insert(input)
+        {
+            events.extend(key.map(FrontendEvent::KeyDown));
+        }
+    }
+
+    fn release(&mut self, instance_id: u32, input: String, events: &mut Vec<FrontendEvent>) {
+        let key = self.keymap.key_for_gamepad(&input);
+        if self
+            .held_inputs
+            .entry(instance_id)
+            .or_default()
+            .remove(&input)
+        {
+            events.extend(key.map(FrontendEvent::KeyUp));
+        }
+    }
+
+    /// Treats each direction of an axis as a button that is held while the axis is pushed past
+    /// the dead zone that way.
+    fn move_axis(
+        &mut self,
+        instance_id: u32,
+        axis: Axis,
+        value: i16,
+        events: &mut Vec<FrontendEvent>,
+    ) {
+        let negative = format!("{}-", axis.string());
+        let positive = format!("{}+", axis.string());
+
+        if value < -AXIS_DEAD_ZONE {
+            self.release(instance_id, positive, events);
+            self.press(instance_id, negative, events);
+        } else if value > AXIS_DEAD_ZONE {
+            self.release(instance_id, negative, events);
+            self.press(instance_id, positive, events);
+        } else {
+            self.release(instance_id, negative, events);
+            self.release(instance_id, positive, events);
+        }
+    }
+}
+
+impl Frontend for SdlFrontend {
+    fn poll_events(&mut self) -> Vec<FrontendEvent> {
+        let sdl_events: Vec<Event> = self.event_pump.poll_iter().collect();
+        let mut events = vec![];
+
+        for event in sdl_events {
+            match event {
+                Event::ControllerDeviceAdded { which, .. } => self.open_controller(which),
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    self.close_controller(which, &mut events)
+                }
+                Event::ControllerButtonDown { which, button, .. } => {
+                    self.press(which, button.string(), &mut events)
+                }
+                Event::ControllerButtonUp { which, button, .. } => {
+                    self.release(which, button.string(), &mut events)
+                }
+                Event::ControllerAxisMotion {
+                    which, axis, value, ..
+                } => self.move_axis(which, axis, value, &mut events),
+                event => events.extend(self.keyboard_event(event)),
+            }
+        }
+
+        events
+    }
+
+    /// Draws the framebuffer, scaling each CHIP-8 pixel up so the current resolution fills the
+    /// window.
+    fn render(&mut self, display: &Display) -> Result<()> {
+        // Worked out every frame, since the window can be resized and the program can switch
+        // resolution at any time
+        let (window_width, window_height) = self.canvas.output_size().map_err(|e| anyhow!(e))?;
+        let viewport = fit_viewport(
+            (window_width, window_height),
+            (display.width() as u32, display.height() as u32),
+            self.integer_scaling,
+        );
+
+        self.canvas.set_draw_color(Color::BLACK);
+        self.canvas.clear();
+        self.canvas.set_draw_color(PALETTE[0]);
+        self.canvas.fill_rect(viewport).map_err(|e| anyhow!(e))?;
+
+        // Each pixel runs up to where the next one starts, so fractional sizes leave no gaps
+        let column =
+            |x: usize| viewport.x() + (x * viewport.width() as usize / display.width()) as i32;
+        let row =
+            |y: usize| viewport.y() + (y * viewport.height() as usize / display.height()) as i32;
+        for (y, pixels) in display.rows().enumerate() {
+            for (x, &pixel) in pixels.iter().enumerate().filter(|(_, &pixel)| pixel != 0) {
+                self.canvas.set_draw_color(PALETTE[pixel as usize]);
+                self.canvas
+                    .fill_rect(Rect::new(
+                        column(x),
+                        row(y),
+                        (column(x + 1) - column(x)) as u32,
+                        (row(y + 1) - row(y)) as u32,
+                    ))
+                    .map_err(|e| anyhow!(e))?;
+            }
+        }
+
+        self.canvas.present();
+        Ok(())
+    }
+}
+
+/// The largest area of `window` with the same shape as `display`, centered, with black bars
+/// along whichever sides are left over. With `integer_scaling`, the area shrinks further to a
+/// whole number of window pixels per CHIP-8 pixel, unless the window is too small for even one.
+fn fit_viewport(window: (u32, u32), display: (u32, u32), integer_scaling: bool) -> Rect {
+    let (window_width, window_height) = window;
+    let (display_width, display_height) = display;
+
+    let scale = (window_width / display_width).min(window_height / display_height);
+    let (width, height) = if integer_scaling && scale > 0 {
+        (display_width * scale, display_height * scale)
+    } else if window_width * display_height > window_height * display_width {
+        (
+            window_height * display_width / display_height,
+            window_height,
+        )
+    } else {
+        (window_width, window_width * display_height / display_width)
+    };
+
+    Rect::new(
+        ((window_width - width) / 2) as i32,
+        ((window_height - height) / 2) as i32,
+        width.max(1),
+        height.max(1),
+    )
+}
+
+fn save_state_slot(keycode: Keycode) -> Option<u8> {
+    Some(match keycode {
+        Keycode::F1 => 1,
+        Keycode::F2 => 2,
+        Keycode::F3 => 3,
+        Keycode::F4 => 4,
+        Keycode::F5 => 5,
+        Keycode::F6 => 6,
+        Keycode::F7 => 7,
+        Keycode::F8 => 8,
+        Keycode::F9 => 9,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::exact_fit((896, 448), (64, 32), true, Rect::new(0, 0, 896, 448))]
+    #[case::high_resolution((896, 448), (128, 64), true, Rect::new(0, 0, 896, 448))]
+    #[case::snapped_down((1000, 460), (64, 32), true, Rect::new(52, 6, 896, 448))]
+    #[case::pillarboxed((1000, 460), (64, 32), false, Rect::new(40, 0, 920, 460))]
+    #[case::letterboxed((1920, 1080), (128, 64), false, Rect::new(0, 60, 1920, 960))]
+    #[case::snapped_and_letterboxed((800, 800), (64, 32), true, Rect::new(16, 208, 768, 384))]
+    #[case::too_small_to_snap((50, 50), (64, 32), true, Rect::new(0, 12, 50, 25))]
+    fn viewport_keeps_the_display_shape(
+        #[case] window: (u32, u32),
+        #[case] display: (u32, u32),
+        #[case] integer_scaling: bool,
+        #[case] expected: Rect,
+    ) {
+        assert_eq!(fit_viewport(window, display, integer_scaling), expected);
+    }
+}
@@ -0,0 +1,191 @@
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+use super::{Frontend, FrontendEvent};
+use crate::{constants::PALETTE, display::Display, keymap::Keymap, virtual_computer::KeyPress};
+use anyhow::Result;
+use crossterm::{
+    cursor,
+    event::{
+        self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    execute,
+    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+};
+
+/// Most terminals only report key presses, so a key is considered released once it hasn't been
+/// pressed (or auto-repeated) for this long.
+const KEY_HOLD_DURATION: Duration = Duration::from_millis(200);
+
+/// Draws the framebuffer with ANSI escape codes, packing two rows of pixels into each character
+/// cell with the upper half block (`▀`). Works over SSH.
+pub struct TerminalFrontend {
+    /// Whether the terminal reports key releases itself (the kitty keyboard protocol)
+    reports_key_releases: bool,
+    keymap: Keymap,
+    held_keys: HashMap<KeyPress, Instant>,
+    last_frame: Option<String>,
+}
+
+impl TerminalFrontend {
+    pub fn new(keymap: Keymap) -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen, cursor::Hide)?;
+
+        let reports_key_releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if reports_key_releases {
+            execute!(
+                io::stdout(),
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+        }
+
+        Ok(Self {
+            reports_key_releases,
+            keymap,
+            held_keys: HashMap::new(),
+            last_frame: None,
+        })
+    }
+
+    fn handle_key_event(&mut self, key_event: KeyEvent) -> Option<FrontendEvent> {
+        let is_ctrl_c = key_event.code == KeyCode::Char('c')
+            && key_event.modifiers.contains(KeyModifiers::CONTROL);
+        if key_event.code == KeyCode::Esc || is_ctrl_c {
+            return Some(FrontendEvent::Quit);
+        }
+
+        let KeyCode::Char(c) = key_event.code else {
+            return None;
+        };
+        let key = self.keymap.key_for(&c.to_string())?;
+
+        match key_event.kind {
+            KeyEventKind::Release => {
+                self.held_keys.remove(&key);
+                Some(FrontendEvent::KeyUp(key))
+            }
+            KeyEventKind::Press | KeyEventKind::Repeat => {
+                let already_held = self.held_keys.insert(key, Instant::now()).is_some();
+                (!already_held).then_some(FrontendEvent::KeyDown(key))
+            }
+        }
+    }
+}
+
+impl Drop for TerminalFrontend {
+    fn drop(&mut self) {
+        if self.reports_key_releases {
+            let _ = execute!(io::stdout(), PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(io::stdout(), cursor::Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+impl Frontend for TerminalFrontend {
+    fn poll_events(&mut self) -> Vec<FrontendEvent> {
+        let mut events = vec![];
+
+        while let Ok(true) = event::poll(Duration::ZERO) {
+            if let Ok(Event::Key(key_event)) = event::read() {
+                events.extend(self.handle_key_event(key_event));
+            }
+        }
+
+        if !self.reports_key_releases {
+            self.held_keys.retain(|&key, pressed_at| {
+                let released = pressed_at.elapsed() > KEY_HOLD_DURATION;
+                if released {
+                    events.push(FrontendEvent::KeyUp(key));
+                }
+                !released
+            });
+        }
+
+        events
+    }
+
+    fn render(&mut self, display: &Display) -> Result<()> {
+        let frame = render_half_blocks(display);
+
+        // Only send a frame when something changed, to keep the output small over slow links
+        if self.last_frame.as_ref() != Some(&frame) {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(frame.as_bytes())?;
+            stdout.flush()?;
+            self.last_frame = Some(frame);
+        }
+
+        Ok(())
+    }
+}
+
+/// Builds the escape sequence that draws the whole framebuffer from the top-left corner of the
+/// terminal. Each character cell is an upper half block whose foreground is the top pixel and
+/// whose background is the bottom pixel, and colors are only sent when they change.
+fn render_half_blocks(display: &Display) -> String {
+    let mut frame = String::from("\x1b[H");
+
+    for y in (0..display.height()).step_by(2) {
+        let mut current_colors = None;
+
+        for x in 0..display.width() {
+            let top = display[y][x];
+            let bottom = if y + 1 < display.height() {
+                display[y + 1][x]
+            } else {
+                0
+            };
+
+            if current_colors != Some((top, bottom)) {
+                let (fg, bg) = (&PALETTE[top as usize], &PALETTE[bottom as usize]);
+                let _ = write!(
+                    frame,
+                    "\x1b[38;2;{};{};{};48;2;{};{};{}m",
+                    fg.r, fg.g, fg.b, bg.r, bg.g, bg.b
+                );
+                current_colors = Some((top, bottom));
+            }
+
+            frame.push('\u{2580}');
+        }
+        frame.push_str("\x1b[0m\r\n");
+    }
+
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn render_half_blocks_packs_two_rows_per_line() {
+        let mut display = Display::default();
+        display.toggle(0, 0, 0b01);
+        display.toggle(1, 1, 0b01);
+        display.toggle(1, 1, 0b10);
+
+        let frame = render_half_blocks(&display);
+        let lines: Vec<&str> = frame.split("\r\n").collect();
+
+        assert_eq!(lines.len(), DISPLAY_HEIGHT as usize / 2 + 1);
+        assert_eq!(
+            lines[0],
+            format!(
+                "\x1b[H\x1b[38;2;255;255;255;48;2;0;0;0m\u{2580}\
+                 \x1b[38;2;0;0;0;48;2;102;34;0m\u{2580}\
+                 \x1b[38;2;0;0;0;48;2;0;0;0m{}\x1b[0m",
+                "\u{2580}".repeat(DISPLAY_WIDTH as usize - 2)
+            )
+        );
+    }
+}
@@ -1,30 +1,82 @@
 use bitmatch::bitmatch;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum InstructionType {
     ClearScreen,
     JumpToMemoryLocation(u16),
     CallSubroutine(u16),
     ReturnFromSubroutine,
-    SkipIfRegisterEqValue { vx: u8, value: u8 },
-    SkipIfRegisterNeqValue { vx: u8, value: u8 },
-    SkipIfRegistersEq { vx: u8, vy: u8 },
-    SkipIfRegistersNeq { vx: u8, vy: u8 },
-    UpdateRegister { vx: u8, value: u8 },
-    AddValueToRegister { vx: u8, value: u8 },
-    CopyRegister { vx: u8, vy: u8 },
-    BitwiseOR { vx: u8, vy: u8 },
-    BitwiseAND { vx: u8, vy: u8 },
-    BitwiseXOR { vx: u8, vy: u8 },
-    AddRegisterToRegister { vx: u8, vy: u8 },
-    SubtractXY { vx: u8, vy: u8 },
-    SubtractYX { vx: u8, vy: u8 },
-    ShiftLeft { vx: u8, vy: u8 },
-    ShiftRight { vx: u8, vy: u8 },
+    SkipIfRegisterEqValue {
+        vx: u8,
+        value: u8,
+    },
+    SkipIfRegisterNeqValue {
+        vx: u8,
+        value: u8,
+    },
+    SkipIfRegistersEq {
+        vx: u8,
+        vy: u8,
+    },
+    SkipIfRegistersNeq {
+        vx: u8,
+        vy: u8,
+    },
+    UpdateRegister {
+        vx: u8,
+        value: u8,
+    },
+    AddValueToRegister {
+        vx: u8,
+        value: u8,
+    },
+    CopyRegister {
+        vx: u8,
+        vy: u8,
+    },
+    BitwiseOR {
+        vx: u8,
+        vy: u8,
+    },
+    BitwiseAND {
+        vx: u8,
+        vy: u8,
+    },
+    BitwiseXOR {
+        vx: u8,
+        vy: u8,
+    },
+    AddRegisterToRegister {
+        vx: u8,
+        vy: u8,
+    },
+    SubtractXY {
+        vx: u8,
+        vy: u8,
+    },
+    SubtractYX {
+        vx: u8,
+        vy: u8,
+    },
+    ShiftLeft {
+        vx: u8,
+        vy: u8,
+    },
+    ShiftRight {
+        vx: u8,
+        vy: u8,
+    },
     SetIndexRegister(u16),
     JumpWithOffset(u16),
-    GenerateRandomNumber { vx: u8, bitmask: u8 },
-    Display { vx: u8, vy: u8, n: u8 },
+    GenerateRandomNumber {
+        vx: u8,
+        bitmask: u8,
+    },
+    Display {
+        vx: u8,
+        vy: u8,
+        n: u8,
+    },
     SkipIfPressedVX(u8),
     SkipIfNotPressedVX(u8),
     FetchDelayTimerToVX(u8),
@@ -36,6 +88,101 @@ pub enum InstructionType {
     BinaryCodedDecimalConversionForVX(u8), // FIXME: unclear name
     StoreVariableRegistersToMemoryUpToVX(u8), // FIXME: long awkward name
     LoadMemoryToVariableRegistersFromVXAddress(u8), // FIXME: same here
+
+    // SUPER-CHIP 1.1
+    ScrollDown(u8),
+    ScrollRight,
+    ScrollLeft,
+    Exit,
+    LowResolution,
+    HighResolution,
+    SetIndexToBigFontCharInVX(u8),
+    StoreVariableRegistersToFlagsUpToVX(u8),
+    LoadFlagsToVariableRegistersUpToVX(u8),
+
+    // XO-CHIP
+    StoreRegisterRange {
+        vx: u8,
+        vy: u8,
+    },
+    LoadRegisterRange {
+        vx: u8,
+        vy: u8,
+    },
+    /// F000 NNNN, where NNNN is the word after the instruction
+    SetIndexRegisterLong,
+    SelectPlanes(u8),
+    LoadAudioPattern,
+    SetPitchToVX(u8),
+    ScrollUp(u8),
+}
+
+impl InstructionType {
+    /// Turns the instruction back into the 16-bit word that `parse_instruction` decodes it from.
+    /// Fields wider than their slot in the opcode are truncated. `SetIndexRegisterLong` only
+    /// encodes the F000 half; the address is the following word.
+    pub fn encode(self) -> u16 {
+        use InstructionType::*;
+
+        let x = |vx: u8| (vx as u16 & 0xF) << 8;
+        let xy = |vx: u8, vy: u8| x(vx) | (vy as u16 & 0xF) << 4;
+        let nnn = |address: u16| address & 0xFFF;
+        let nn = |value: u8| value as u16;
+        let n = |value: u8| value as u16 & 0xF;
+
+        match self {
+            ClearScreen => 0x00E0,
+            JumpToMemoryLocation(address) => 0x1000 | nnn(address),
+            CallSubroutine(address) => 0x2000 | nnn(address),
+            ReturnFromSubroutine => 0x00EE,
+            SkipIfRegisterEqValue { vx, value } => 0x3000 | x(vx) | nn(value),
+            SkipIfRegisterNeqValue { vx, value } => 0x4000 | x(vx) | nn(value),
+            SkipIfRegistersEq { vx, vy } => 0x5000 | xy(vx, vy),
+            SkipIfRegistersNeq { vx, vy } => 0x9000 | xy(vx, vy),
+            UpdateRegister { vx, value } => 0x6000 | x(vx) | nn(value),
+            AddValueToRegister { vx, value } => 0x7000 | x(vx) | nn(value),
+            CopyRegister { vx, vy } => 0x8000 | xy(vx, vy),
+            BitwiseOR { vx, vy } => 0x8001 | xy(vx, vy),
+            BitwiseAND { vx, vy } => 0x8002 | xy(vx, vy),
+            BitwiseXOR { vx, vy } => 0x8003 | xy(vx, vy),
+            AddRegisterToRegister { vx, vy } => 0x8004 | xy(vx, vy),
+            SubtractXY { vx, vy } => 0x8005 | xy(vx, vy),
+            SubtractYX { vx, vy } => 0x8007 | xy(vx, vy),
+            ShiftRight { vx, vy } => 0x8006 | xy(vx, vy),
+            ShiftLeft { vx, vy } => 0x800E | xy(vx, vy),
+            SetIndexRegister(address) => 0xA000 | nnn(address),
+            JumpWithOffset(address) => 0xB000 | nnn(address),
+            GenerateRandomNumber { vx, bitmask } => 0xC000 | x(vx) | nn(bitmask),
+            Display { vx, vy, n: rows } => 0xD000 | xy(vx, vy) | n(rows),
+            SkipIfPressedVX(vx) => 0xE09E | x(vx),
+            SkipIfNotPressedVX(vx) => 0xE0A1 | x(vx),
+            FetchDelayTimerToVX(vx) => 0xF007 | x(vx),
+            SetDelayTimerToVX(vx) => 0xF015 | x(vx),
+            SetSoundTimerToVX(vx) => 0xF018 | x(vx),
+            AddToIndexFromVX(vx) => 0xF01E | x(vx),
+            WaitForKeyInVX(vx) => 0xF00A | x(vx),
+            SetIndexToFontCharInVX(vx) => 0xF029 | x(vx),
+            BinaryCodedDecimalConversionForVX(vx) => 0xF033 | x(vx),
+            StoreVariableRegistersToMemoryUpToVX(vx) => 0xF055 | x(vx),
+            LoadMemoryToVariableRegistersFromVXAddress(vx) => 0xF065 | x(vx),
+            ScrollDown(rows) => 0x00C0 | n(rows),
+            ScrollRight => 0x00FB,
+            ScrollLeft => 0x00FC,
+            Exit => 0x00FD,
+            LowResolution => 0x00FE,
+            HighResolution => 0x00FF,
+            SetIndexToBigFontCharInVX(vx) => 0xF030 | x(vx),
+            StoreVariableRegistersToFlagsUpToVX(vx) => 0xF075 | x(vx),
+            LoadFlagsToVariableRegistersUpToVX(vx) => 0xF085 | x(vx),
+            StoreRegisterRange { vx, vy } => 0x5002 | xy(vx, vy),
+            LoadRegisterRange { vx, vy } => 0x5003 | xy(vx, vy),
+            SetIndexRegisterLong => 0xF000,
+            SelectPlanes(planes) => 0xF001 | x(planes),
+            LoadAudioPattern => 0xF002,
+            SetPitchToVX(vx) => 0xF03A | x(vx),
+            ScrollUp(rows) => 0x00D0 | n(rows),
+        }
+    }
 }
 
 #[bitmatch]
@@ -82,6 +229,22 @@ pub fn parse_instruction(instr: u16) -> Option<InstructionType> {
         "1111_????_0110_0101" => Some(InstructionType::LoadMemoryToVariableRegistersFromVXAddress(
             x,
         )),
+        "0000_0000_1100_????" => Some(InstructionType::ScrollDown(n)),
+        "0000_0000_1111_1011" => Some(InstructionType::ScrollRight),
+        "0000_0000_1111_1100" => Some(InstructionType::ScrollLeft),
+        "0000_0000_1111_1101" => Some(InstructionType::Exit),
+        "0000_0000_1111_1110" => Some(InstructionType::LowResolution),
+        "0000_0000_1111_1111" => Some(InstructionType::HighResolution),
+        "1111_????_0011_0000" => Some(InstructionType::SetIndexToBigFontCharInVX(x)),
+        "1111_????_0111_0101" => Some(InstructionType::StoreVariableRegistersToFlagsUpToVX(x)),
+        "1111_????_1000_0101" => Some(InstructionType::LoadFlagsToVariableRegistersUpToVX(x)),
+        "0101_????_????_0010" => Some(InstructionType::StoreRegisterRange { vx: x, vy: y }),
+        "0101_????_????_0011" => Some(InstructionType::LoadRegisterRange { vx: x, vy: y }),
+        "1111_0000_0000_0000" => Some(InstructionType::SetIndexRegisterLong),
+        "1111_????_0000_0001" => Some(InstructionType::SelectPlanes(x)),
+        "1111_0000_0000_0010" => Some(InstructionType::LoadAudioPattern),
+        "1111_????_0011_1010" => Some(InstructionType::SetPitchToVX(x)),
+        "0000_0000_1101_????" => Some(InstructionType::ScrollUp(n)),
         _ => None,
     }
 }
@@ -142,11 +305,37 @@ mod tests {
         0xFA65,
         Some(InstructionType::LoadMemoryToVariableRegistersFromVXAddress(0xA))
     )]
+    #[case(0x00C4, Some(InstructionType::ScrollDown(4)))]
+    #[case(0x00FB, Some(InstructionType::ScrollRight))]
+    #[case(0x00FC, Some(InstructionType::ScrollLeft))]
+    #[case(0x00FD, Some(InstructionType::Exit))]
+    #[case(0x00FE, Some(InstructionType::LowResolution))]
+    #[case(0x00FF, Some(InstructionType::HighResolution))]
+    #[case(0xF330, Some(InstructionType::SetIndexToBigFontCharInVX(3)))]
+    #[case(0xF775, Some(InstructionType::StoreVariableRegistersToFlagsUpToVX(7)))]
+    #[case(0xF285, Some(InstructionType::LoadFlagsToVariableRegistersUpToVX(2)))]
+    #[case(0x5352, Some(InstructionType::StoreRegisterRange { vx: 3, vy: 5 }))]
+    #[case(0x5A13, Some(InstructionType::LoadRegisterRange { vx: 0xA, vy: 1 }))]
+    #[case(0xF000, Some(InstructionType::SetIndexRegisterLong))]
+    #[case(0xF201, Some(InstructionType::SelectPlanes(2)))]
+    #[case(0xF002, Some(InstructionType::LoadAudioPattern))]
+    #[case(0xF63A, Some(InstructionType::SetPitchToVX(6)))]
+    #[case(0x00D3, Some(InstructionType::ScrollUp(3)))]
     #[case(0x0000, None)]
     fn parse_instruction_test(#[case] input: u16, #[case] expected: Option<InstructionType>) {
         assert_eq!(parse_instruction(input), expected);
     }
 
+    #[test]
+    fn encode_inverts_parse_for_every_word() {
+        for word in 0..=u16::MAX {
+            if let Some(instr) = parse_instruction(word) {
+                assert_eq!(instr.encode(), word, "{:?}", instr);
+                assert_eq!(parse_instruction(instr.encode()), Some(instr));
+            }
+        }
+    }
+
     #[test]
     fn extract_parts_works() {
         let (opcode, x, y, n, nn, nnn) = extract_parts(0x39A0);
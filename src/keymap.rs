@@ -0,0 +1,369 @@
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::virtual_computer::KeyPress;
+
+/// The CHIP-8 keypad, laid out the way it was on the COSMAC VIP. Presets list host keys in the
+/// same positions.
+const KEYPAD_LAYOUT: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+/// Built-in layouts that all put the keypad on the same physical 4x4 block of keys: the left
+/// side of the number row and the three rows below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeymapPreset {
+    Qwerty,
+    Azerty,
+    Dvorak,
+}
+
+impl KeymapPreset {
+    fn layout(self) -> [[&'static str; 4]; 4] {
+        match self {
+            KeymapPreset::Qwerty => [
+                ["1", "2", "3", "4"],
+                ["Q", "W", "E", "R"],
+                ["A", "S", "D", "F"],
+                ["Z", "X", "C", "V"],
+            ],
+            KeymapPreset::Azerty => [
+                ["1", "2", "3", "4"],
+                ["A", "Z", "E", "R"],
+                ["Q", "S", "D", "F"],
+                ["W", "X", "C", "V"],
+            ],
+            KeymapPreset::Dvorak => [
+                ["1", "2", "3", "4"],
+                ["'", ",", ".", "P"],
+                ["A", "O", "E", "U"],
+                [";", "Q", "J", "K"],
+            ],
+        }
+    }
+}
+
+/// What a gamepad does out of the box: the D-pad and left stick press 2/4/6/8, the directions
+/// most games use, and the face buttons press the keys games most often use for actions.
+const DEFAULT_GAMEPAD: [(u8, &[&str]); 10] = [
+    (0x2, &["dpup", "lefty-"]),
+    (0x8, &["dpdown", "lefty+"]),
+    (0x4, &["dpleft", "leftx-"]),
+    (0x6, &["dpright", "leftx+"]),
+    (0x5, &["a"]),
+    (0x0, &["b"]),
+    (0xA, &["x"]),
+    (0xB, &["y"]),
+    (0xE, &["back"]),
+    (0xF, &["start"]),
+];
+
+/// The names of the host inputs that press each CHIP-8 key, indexed by key. Names are compared
+/// ignoring case, and a key can have any number of them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Bindings([Vec<String>; 16]);
+
+impl Bindings {
+    fn from_layout(layout: [[&str; 4]; 4]) -> Self {
+        let mut bindings = Bindings::default();
+        for (keys, names) in KEYPAD_LAYOUT.iter().zip(layout) {
+            for (&key, name) in keys.iter().zip(names) {
+                bindings.0[key as usize] = vec![name.to_string()];
+            }
+        }
+        bindings
+    }
+
+    fn key_for(&self, name: &str) -> Option<KeyPress> {
+        let index = self.0.iter().position(|names| {
+            names
+                .iter()
+                .any(|bound| bound.to_lowercase() == name.to_lowercase())
+        })?;
+        KeyPress::try_from(index as u8).ok()
+    }
+
+    /// Replaces the bindings of every key in `keys`. Host keys that get rebound are taken off
+    /// whichever key they were on before, and everything else is left alone.
+    fn rebind(&mut self, keys: &BTreeMap<u8, Vec<String>>) {
+        let rebound: Vec<String> = keys
+            .values()
+            .flatten()
+            .map(|name| name.to_lowercase())
+            .collect();
+        for names in &mut self.0 {
+            names.retain(|name| !rebound.contains(&name.to_lowercase()));
+        }
+        for (&key, names) in keys {
+            self.0[key as usize] = names.clone();
+        }
+    }
+}
+
+/// Which host inputs press each CHIP-8 key.
+///
+/// Keyboard keys are named the way SDL names them (`Q`, `1`, `Keypad 5`, `Space`). Gamepad inputs
+/// use SDL's game controller names: buttons like `a`, `start`, and `dpup`, and axes with the
+/// direction they're pushed in, like `leftx-` or `righttrigger+`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    keyboard: Bindings,
+    gamepad: Bindings,
+}
+
+impl Keymap {
+    /// A keymap with the preset's keyboard layout and the default gamepad bindings.
+    pub fn from_preset(preset: KeymapPreset) -> Self {
+        let mut gamepad = Bindings::default();
+        for (key, names) in DEFAULT_GAMEPAD {
+            gamepad.0[key as usize] = names.iter().map(|name| name.to_string()).collect();
+        }
+
+        Self {
+            keyboard: Bindings::from_layout(preset.layout()),
+            gamepad,
+        }
+    }
+
+    /// The CHIP-8 key that a keyboard key is bound to, if any.
+    pub fn key_for(&self, host_key: &str) -> Option<KeyPress> {
+        self.keyboard.key_for(host_key)
+    }
+
+    /// The CHIP-8 key that a gamepad button or axis direction is bound to, if any.
+    pub fn key_for_gamepad(&self, input: &str) -> Option<KeyPress> {
+        self.gamepad.key_for(input)
+    }
+
+    /// Builds the keymap for a ROM from, in increasing priority: the QWERTY preset, the global
+    /// config file, the ROM's own config file (`pong.ch8` uses `pong.toml`), and the `--keymap`
+    /// option, which is either a preset name or the path to a config file.
+    pub fn resolve(rom_path: &Path, option: Option<&str>) -> Result<Self> {
+        let mut keymap = Keymap::default();
+
+        if let Some(global) = global_config_path().filter(|path| path.exists()) {
+            keymap.apply(&KeymapConfig::load(&global)?);
+        }
+
+        let per_rom = rom_path.with_extension("toml");
+        if per_rom.exists() {
+            keymap.apply(&KeymapConfig::load(&per_rom)?);
+        }
+
+        if let Some(option) = option {
+            match KeymapPreset::from_str(option, true) {
+                Ok(preset) => keymap.keyboard = Bindings::from_layout(preset.layout()),
+                Err(_) => keymap.apply(&KeymapConfig::load(Path::new(option))?),
+            }
+        }
+
+        Ok(keymap)
+    }
+
+    /// Layers a config on top: its preset replaces every keyboard binding, and then each key it
+    /// lists replaces that key's bindings.
+    fn apply(&mut self, config: &KeymapConfig) {
+        if let Some(preset) = config.preset {
+            self.keyboard = Bindings::from_layout(preset.layout());
+        }
+        self.keyboard.rebind(&config.keys);
+        self.gamepad.rebind(&config.gamepad);
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::from_preset(KeymapPreset::Qwerty)
+    }
+}
+
+/// Where the keymap shared by every ROM lives: `$XDG_CONFIG_HOME/chip8/config.toml`, falling back
+/// to `~/.config/chip8/config.toml`.
+fn global_config_path() -> Option<PathBuf> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_dir.join("chip8").join("config.toml"))
+}
+
+/// The `[keymap]` and `[gamepad]` tables of a config file. Keys are CHIP-8 key digits, each
+/// bound to one host input or a list of them:
+///
+/// ```toml
+/// [keymap]
+/// preset = "azerty"
+/// 5 = ["Z", "Up"]
+/// 8 = "Down"
+///
+/// [gamepad]
+/// 5 = ["a", "righttrigger+"]
+/// ```
+#[derive(Debug, Default, PartialEq, Eq)]
+struct KeymapConfig {
+    preset: Option<KeymapPreset>,
+    keys: BTreeMap<u8, Vec<String>>,
+    gamepad: BTreeMap<u8, Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct ConfigFile {
+    #[serde(default)]
+    keymap: RawKeymapConfig,
+    #[serde(default)]
+    gamepad: BTreeMap<String, HostKeys>,
+}
+
+#[derive(Default, Deserialize)]
+struct RawKeymapConfig {
+    preset: Option<KeymapPreset>,
+    #[serde(flatten)]
+    keys: BTreeMap<String, HostKeys>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum HostKeys {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeymapConfig {
+    fn load(path: &Path) -> Result<Self> {
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("Couldn't open {}", path.display()))?;
+        Self::parse(&source).with_context(|| format!("Couldn't read {}", path.display()))
+    }
+
+    fn parse(source: &str) -> Result<Self> {
+        let file = toml::from_str::<ConfigFile>(source)?;
+
+        Ok(Self {
+            preset: file.keymap.preset,
+            keys: parse_keys(file.keymap.keys)?,
+            gamepad: parse_keys(file.gamepad)?,
+        })
+    }
+}
+
+fn parse_keys(raw: BTreeMap<String, HostKeys>) -> Result<BTreeMap<u8, Vec<String>>> {
+    let mut keys = BTreeMap::new();
+    for (key, host_keys) in raw {
+        let key = u8::from_str_radix(&key, 16)
+            .ok()
+            .filter(|&key| key <= 0xF)
+            .ok_or_else(|| anyhow!("{} is not a CHIP-8 key, expected 0-F", key))?;
+        let host_keys = match host_keys {
+            HostKeys::One(host_key) => vec![host_key],
+            HostKeys::Many(host_keys) => host_keys,
+        };
+        keys.insert(key, host_keys);
+    }
+    Ok(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(KeymapPreset::Qwerty, "X", Some(KeyPress::Key0))]
+    #[case(KeymapPreset::Qwerty, "v", Some(KeyPress::KeyF))]
+    #[case(KeymapPreset::Azerty, "A", Some(KeyPress::Key4))]
+    #[case(KeymapPreset::Azerty, "Q", Some(KeyPress::Key7))]
+    #[case(KeymapPreset::Dvorak, "O", Some(KeyPress::Key8))]
+    #[case(KeymapPreset::Dvorak, "S", None)]
+    fn presets_bind_the_same_physical_keys(
+        #[case] preset: KeymapPreset,
+        #[case] host_key: &str,
+        #[case] expected: Option<KeyPress>,
+    ) {
+        assert_eq!(Keymap::from_preset(preset).key_for(host_key), expected);
+    }
+
+    #[test]
+    fn config_layers_keys_over_its_preset() {
+        let config = KeymapConfig::parse(
+            r#"
+            [keymap]
+            preset = "azerty"
+            5 = ["Z", "Up"]
+            a = "Space"
+            "#,
+        )
+        .unwrap();
+        let mut keymap = Keymap::default();
+
+        keymap.apply(&config);
+
+        assert_eq!(keymap.key_for("Up"), Some(KeyPress::Key5));
+        assert_eq!(keymap.key_for("z"), Some(KeyPress::Key5));
+        assert_eq!(keymap.key_for("space"), Some(KeyPress::KeyA));
+        assert_eq!(keymap.key_for("W"), None);
+        assert_eq!(keymap.key_for("Q"), Some(KeyPress::Key7));
+    }
+
+    #[test]
+    fn rebinding_a_preset_key_moves_it() {
+        let config = KeymapConfig::parse("[keymap]\n5 = \"q\"").unwrap();
+        let mut keymap = Keymap::default();
+
+        keymap.apply(&config);
+
+        assert_eq!(keymap.key_for("Q"), Some(KeyPress::Key5));
+        assert_eq!(keymap.key_for("W"), None);
+        assert_eq!(keymap.key_for("E"), Some(KeyPress::Key6));
+    }
+
+    #[rstest]
+    #[case("[keymap]\nG = \"Q\"", "G is not a CHIP-8 key, expected 0-F")]
+    #[case("[keymap]\n10 = \"Q\"", "10 is not a CHIP-8 key, expected 0-F")]
+    fn bad_keys_are_rejected(#[case] source: &str, #[case] expected: &str) {
+        assert_eq!(
+            KeymapConfig::parse(source).unwrap_err().to_string(),
+            expected
+        );
+    }
+
+    #[test]
+    fn files_without_a_keymap_change_nothing() {
+        assert_eq!(KeymapConfig::parse("").unwrap(), KeymapConfig::default());
+    }
+
+    #[test]
+    fn gamepad_bindings_are_separate_from_the_keyboard() {
+        let config = KeymapConfig::parse(
+            r#"
+            [keymap]
+            preset = "dvorak"
+
+            [gamepad]
+            5 = ["a", "righttrigger+"]
+            "#,
+        )
+        .unwrap();
+        let mut keymap = Keymap::default();
+
+        keymap.apply(&config);
+
+        assert_eq!(
+            keymap.key_for_gamepad("RightTrigger+"),
+            Some(KeyPress::Key5)
+        );
+        assert_eq!(keymap.key_for_gamepad("dpup"), Some(KeyPress::Key2));
+        assert_eq!(keymap.key_for_gamepad("a"), Some(KeyPress::Key5));
+        assert_eq!(keymap.key_for("a"), Some(KeyPress::Key7));
+    }
+}
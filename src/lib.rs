@@ -1,93 +1,367 @@
-mod constants;
-mod errors;
-mod instruction_parser;
-mod virtual_computer;
+pub mod assembler;
+pub mod audio;
+pub mod constants;
+pub mod debugger;
+pub mod disassembler;
+pub mod display;
+pub mod errors;
+pub mod frame_pacer;
+pub mod frontend;
+pub mod instruction_parser;
+pub mod keymap;
+pub mod movie;
+pub mod palette;
+pub mod quirks;
+pub mod recorder;
+pub mod rewind;
+pub mod save_state;
+pub mod screenshot;
+pub mod virtual_computer;
 
 use std::{
-    collections::HashSet,
-    fs::File,
-    time::{Duration, Instant},
+    fs, io,
+    path::{Path, PathBuf},
 };
 
-use anyhow::Result;
-use constants::{BACKGROUND_COLOR, WINDOW_HEIGHT, WINDOW_WIDTH};
-use instruction_parser::parse_instruction;
-use sdl2::{event::Event, keyboard::Keycode};
-use virtual_computer::{KeyPress, VirtualComputer};
+use anyhow::{anyhow, Context, Result};
+use audio::{AudioBackend, NullAudioSink, ToneSettings};
+use debugger::{Debugger, DebuggerAction};
+use errors::FaultPolicy;
+use frame_pacer::{FramePacer, FRAMES_PER_SECOND};
+use frontend::{Backend, FrontendEvent, WindowOptions};
+use keymap::Keymap;
+use movie::{Movie, MovieRecorder};
+use palette::Palette;
+use quirks::Quirks;
+use recorder::Recorder;
+use rewind::Rewinder;
+use virtual_computer::{CompatibilityMode, Sound, VirtualComputer};
 
-pub fn run(rom_file: File) -> Result<()> {
-    let sdl_context = sdl2::init().unwrap();
-    let video_subsystem = sdl_context.video().unwrap();
+/// Settings for a single run of the emulator.
+pub struct RunOptions {
+    pub backend: Backend,
+    pub window: WindowOptions,
+    /// Which host keys press which CHIP-8 keys
+    pub keymap: Keymap,
+    pub compatibility_mode: CompatibilityMode,
+    /// Starts out as the compatibility mode's quirks, with any overrides applied
+    pub quirks: Quirks,
+    /// Overrides the compatibility mode's stack depth
+    pub stack_depth: Option<usize>,
+    pub audio: AudioBackend,
+    /// Where the WAV audio backend writes to
+    pub wav_path: PathBuf,
+    pub tone: ToneSettings,
+    /// How many instructions run in each 60hz frame
+    pub instructions_per_frame: u32,
+    /// Seed for CXNN's random numbers. A random seed is used when absent.
+    pub seed: Option<u64>,
+    /// A save state to resume from instead of starting the ROM fresh
+    pub load_state: Option<PathBuf>,
+    /// Starts paused at the debugger's prompt, reading commands from stdin
+    pub debug: bool,
+    /// What happens when the program faults
+    pub on_fault: FaultPolicy,
+    /// Colors used for screenshots and recordings
+    pub palette: Palette,
+    /// How many image pixels wide and tall each CHIP-8 pixel is in screenshots and recordings
+    pub image_scale: u32,
+    /// Takes a screenshot once this many frames have run. The null backend quits afterwards.
+    pub screenshot_at_frame: Option<u64>,
+    /// Where that screenshot goes, instead of next to the ROM
+    pub screenshot_path: Option<PathBuf>,
+    /// Records every frame from the start, to a GIF or a directory of frames
+    pub record: Option<PathBuf>,
+    /// Stops after this many frames, for headless runs of ROMs that never exit
+    pub max_frames: Option<u64>,
+    /// How many seconds of play Backspace can rewind through, or 0 to keep no history
+    pub rewind_seconds: u32,
+    /// How many frames apart the rewind snapshots are
+    pub rewind_interval: u32,
+    /// Records the keypad during every frame, along with everything else needed to replay the run
+    pub record_movie: Option<PathBuf>,
+    /// Replays a movie's inputs with its settings, in place of the ones above. The null backend
+    /// quits when the movie ends, and the others hand control back to the keyboard.
+    pub play_movie: Option<PathBuf>,
+}
+
+/// Where the numbered save state slot for a ROM lives: next to the ROM, e.g. `pong.state1`.
+pub fn save_state_slot_path(rom_path: &Path, slot: u8) -> PathBuf {
+    rom_path.with_extension(format!("state{}", slot))
+}
 
-    let window = video_subsystem
-        .window("chip8", WINDOW_WIDTH, WINDOW_HEIGHT)
-        .position_centered()
-        .build()
-        .unwrap();
+/// Where a screenshot taken after `frame` frames goes: next to the ROM, e.g. `pong.frame120.png`.
+pub fn screenshot_path(rom_path: &Path, frame: u64) -> PathBuf {
+    rom_path.with_extension(format!("frame{}.png", frame))
+}
+
+/// Where a recording started after `frame` frames goes: next to the ROM, e.g. `pong.frame120.gif`.
+pub fn recording_path(rom_path: &Path, frame: u64) -> PathBuf {
+    rom_path.with_extension(format!("frame{}.gif", frame))
+}
 
-    let mut canvas = window.into_canvas().build().unwrap();
+pub fn run(rom_path: &Path, options: RunOptions) -> Result<()> {
+    let may_debug = options.debug || options.on_fault == FaultPolicy::Debug;
+    // Skipping past a fault in the debugger isn't something a movie can record
+    if may_debug && options.record_movie.is_some() {
+        return Err(anyhow!(
+            "Movies can't be recorded with the debugger attached"
+        ));
+    }
+    if may_debug && options.backend == Backend::Terminal {
+        return Err(anyhow!(
+            "The debugger reads from the terminal, so it can't be used with the terminal backend"
+        ));
+    }
 
-    canvas.set_draw_color(*BACKGROUND_COLOR);
-    canvas.clear();
-    canvas.present();
+    let rom =
+        fs::read(rom_path).with_context(|| format!("Couldn't open {}", rom_path.display()))?;
 
-    let mut event_pump = sdl_context.event_pump().unwrap();
+    // SDL can only be initialized once, so the frontend and audio share a context
+    let needs_sdl = options.backend == Backend::Sdl || options.audio == AudioBackend::Sdl;
+    let sdl_context = if needs_sdl {
+        Some(sdl2::init().map_err(|e| anyhow!("Couldn't initialize SDL: {}", e))?)
+    } else {
+        None
+    };
 
-    let mut vc = VirtualComputer::from_rom_file(rom_file)?;
+    let mut frontend =
+        options
+            .backend
+            .create(sdl_context.as_ref(), options.keymap.clone(), options.window)?;
+    // A machine without a sound card can still run games, just silently
+    let mut audio =
+        match options
+            .audio
+            .create(sdl_context.as_ref(), &options.wav_path, options.tone)
+        {
+            Ok(audio) => audio,
+            Err(e) if options.audio == AudioBackend::Sdl => {
+                eprintln!("{:#}, continuing without sound", e);
+                Box::new(NullAudioSink)
+            }
+            Err(e) => return Err(e),
+        };
 
-    let mut keys_pressed = HashSet::new();
+    let mut vc = VirtualComputer::from_rom_bytes(&rom, options.compatibility_mode)?;
+    vc.set_quirks(options.quirks);
+    if let Some(stack_depth) = options.stack_depth {
+        vc.set_stack_depth(stack_depth);
+    }
 
-    let mut last_ticked = Instant::now();
+    // Always seeded explicitly, so that movies know what seed to replay with
+    let seed = options.seed.unwrap_or_else(rand::random);
+    vc.set_seed(seed);
+    let mut instructions_per_frame = options.instructions_per_frame;
+    let mut on_fault = options.on_fault;
 
-    'running: loop {
-        // Check if it is time to tick the timers at 60hz
-        let elapsed_time = last_ticked.elapsed();
-        if elapsed_time.as_millis() > (1000.0 / 60.0) as u128 {
-            last_ticked = Instant::now();
-            vc.decrement_timers();
+    let mut playback = match &options.play_movie {
+        Some(path) => {
+            let movie = Movie::load(path)?;
+            vc = movie.start(&rom)?;
+            instructions_per_frame = movie.instructions_per_frame;
+            on_fault = movie.on_fault;
+            Some(movie)
         }
+        None => None,
+    };
+    let mut movie_recorder = options.record_movie.clone().map(|path| {
+        MovieRecorder::new(
+            path,
+            Movie::new(&rom, &vc, seed, instructions_per_frame, on_fault),
+        )
+    });
+
+    if let Some(state_path) = &options.load_state {
+        let state = fs::read(state_path)
+            .with_context(|| format!("Couldn't open {}", state_path.display()))?;
+        vc = VirtualComputer::load_state(&state)?;
+    }
+
+    let mut debugger = options
+        .debug
+        .then(|| Debugger::new(io::stdin().lock(), io::stdout()));
+
+    let mut pacer = FramePacer::new();
+    let mut frame = 0;
+    let mut recorder = match &options.record {
+        Some(path) => Some(Recorder::create(
+            path,
+            options.image_scale,
+            options.palette,
+        )?),
+        None => None,
+    };
 
+    let mut rewinder = Rewinder::new(
+        (options.rewind_seconds.saturating_mul(FRAMES_PER_SECOND) / options.rewind_interval)
+            as usize,
+        options.rewind_interval,
+    );
+    let mut rewinding = false;
+
+    'running: loop {
         // 1. Input
-        for event in event_pump.poll_iter() {
+        for event in frontend.poll_events() {
             match event {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
-                    ..
-                } => break 'running,
-                Event::KeyDown {
-                    keycode: Some(keycode),
-                    ..
-                } => {
-                    if let Some(key) = KeyPress::from_sdl_key(keycode) {
-                        keys_pressed.insert(key);
+                FrontendEvent::Quit => break 'running,
+                // Movies being played back supply their own input
+                FrontendEvent::KeyDown(key) if playback.is_none() => vc.press_key(key),
+                FrontendEvent::KeyUp(key) if playback.is_none() => vc.release_key(key),
+                FrontendEvent::KeyDown(_) | FrontendEvent::KeyUp(_) => {}
+                // A bad slot shouldn't end the game, so these only report failures
+                FrontendEvent::SaveState(slot) => {
+                    let path = save_state_slot_path(rom_path, slot);
+                    if let Err(e) = fs::write(&path, vc.save_state()) {
+                        eprintln!("Couldn't save state to {}: {}", path.display(), e);
+                    }
+                }
+                // Jumping to another point would desync the movie from the run
+                FrontendEvent::LoadState(_) if playback.is_some() || movie_recorder.is_some() => {
+                    eprintln!("Save states can't be loaded while a movie is recording or playing");
+                }
+                FrontendEvent::LoadState(slot) => {
+                    let path = save_state_slot_path(rom_path, slot);
+                    match fs::read(&path)
+                        .map_err(anyhow::Error::from)
+                        .and_then(|state| VirtualComputer::load_state(&state))
+                    {
+                        // Keys are still held on the host regardless of what the state says
+                        Ok(mut loaded) => {
+                            loaded.set_keypad(vc.keypad());
+                            vc = loaded;
+                        }
+                        Err(e) => eprintln!("Couldn't load state from {}: {}", path.display(), e),
                     }
                 }
-                Event::KeyUp {
-                    keycode: Some(keycode),
-                    ..
-                } => {
-                    if let Some(key) = KeyPress::from_sdl_key(keycode) {
-                        keys_pressed.take(&key);
+                FrontendEvent::Screenshot => {
+                    let path = screenshot_path(rom_path, frame);
+                    if let Err(e) = screenshot::save_png(
+                        &path,
+                        vc.display(),
+                        options.image_scale,
+                        options.palette,
+                    ) {
+                        eprintln!("{:#}", e);
                     }
                 }
-                _ => {}
+                // Rewinding would also desync a movie, since it only records forward input
+                FrontendEvent::StartRewinding if playback.is_some() || movie_recorder.is_some() => {
+                    eprintln!("Can't rewind while a movie is recording or playing");
+                }
+                FrontendEvent::StartRewinding => rewinding = true,
+                FrontendEvent::StopRewinding => rewinding = false,
+                FrontendEvent::ToggleRecording => match recorder.take() {
+                    Some(recorder) => recorder.finish()?,
+                    None => {
+                        let path = recording_path(rom_path, frame);
+                        recorder = Some(Recorder::create(
+                            &path,
+                            options.image_scale,
+                            options.palette,
+                        )?);
+                    }
+                },
             }
         }
 
         // 2. Update
-        if let Some(instr_raw) = vc.fetch_instruction_and_increment_pc() {
-            match parse_instruction(instr_raw) {
-                Some(instr) => vc.execute_instruction(instr, &mut canvas, &keys_pressed),
-                None => {} // None => eprintln!("Unknown raw instruction: {:#06x}", instr_raw),
+        if rewinding {
+            // Once the buffer runs out, the oldest frame stays on screen until the key is let go
+            if let Some(state) = rewinder.rewind() {
+                let mut state = state?;
+                state.set_keypad(vc.keypad());
+                vc = state;
+            }
+            audio.update(Sound::Silent)?;
+            frontend.render(vc.display())?;
+            if frontend.paces_to_real_time() {
+                pacer.wait_for_next_frame();
             }
+            continue 'running;
         }
+        if let Some(movie) = &playback {
+            match movie.keypads.get(frame as usize) {
+                Some(&keypad) => vc.set_keypad(keypad),
+                None if options.backend == Backend::Null => break 'running,
+                None => {
+                    eprintln!("Movie finished after {} frames", frame);
+                    playback = None;
+                }
+            }
+        }
+        if let Some(movie_recorder) = movie_recorder.as_mut() {
+            movie_recorder.add_frame(vc.keypad());
+        }
+        rewinder.record(&vc);
+        match debugger.as_mut() {
+            Some(debugger) => {
+                let action = debugger.run_frame(&mut vc, instructions_per_frame)?;
+                if action == DebuggerAction::Quit {
+                    break 'running;
+                }
+            }
+            None => match on_fault {
+                FaultPolicy::Halt => vc.run_frame(instructions_per_frame)?,
+                FaultPolicy::Debug => {
+                    if let Err(fault) = vc.run_frame(instructions_per_frame) {
+                        let debugger =
+                            debugger.insert(Debugger::new(io::stdin().lock(), io::stdout()));
+                        debugger.break_on_fault(fault)?;
+                    }
+                }
+                FaultPolicy::Ignore => run_frame_ignoring_faults(&mut vc, instructions_per_frame),
+            },
+        }
+        audio.update(vc.sound())?;
+        frame += 1;
 
         // 3. Render
-        canvas.present();
-        // std::thread::sleep(Duration::new(0, 1_000_000u32 / 60));
-        std::thread::sleep(Duration::from_millis(7));
+        frontend.render(vc.display())?;
+        if let Some(recorder) = recorder.as_mut() {
+            recorder.add_frame(vc.display())?;
+        }
+
+        if options.screenshot_at_frame == Some(frame) {
+            let path = options
+                .screenshot_path
+                .clone()
+                .unwrap_or_else(|| screenshot_path(rom_path, frame));
+            screenshot::save_png(&path, vc.display(), options.image_scale, options.palette)?;
+            if options.backend == Backend::Null {
+                break 'running;
+            }
+        }
+
+        if vc.has_exited() || options.max_frames == Some(frame) {
+            break 'running;
+        }
+
+        if frontend.paces_to_real_time() {
+            pacer.wait_for_next_frame();
+        }
     }
 
+    if let Some(recorder) = recorder {
+        recorder.finish()?;
+    }
+    if let Some(movie_recorder) = movie_recorder {
+        movie_recorder.finish()?;
+    }
+    audio.finish()?;
     Ok(())
 }
+
+/// Like `VirtualComputer::run_frame`, but steps over any instruction that faults instead of
+/// ending the frame early, so the timers keep ticking for programs that fault every frame.
+fn run_frame_ignoring_faults(vc: &mut VirtualComputer, instructions_per_frame: u32) {
+    for _ in 0..instructions_per_frame {
+        if vc.has_exited() {
+            break;
+        }
+        if vc.step().is_err() {
+            vc.skip_faulting_instruction();
+        }
+    }
+    vc.decrement_timers();
+}
@@ -1,30 +1,281 @@
-use std::{fs::File, path::Path, process};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
-use anyhow::Result;
-use chip8::run;
-use clap::Parser;
+use anyhow::{Context, Result};
+use chip8::{
+    assembler::{assemble_file, default_output_path},
+    audio::{AudioBackend, ToneSettings, Waveform},
+    disassembler::{disassemble, disassemble_recursive, Syntax},
+    errors::FaultPolicy,
+    frame_pacer::instructions_per_frame_from_hz,
+    frontend::{Backend, WindowOptions},
+    keymap::Keymap,
+    palette::Palette,
+    quirks::Quirks,
+    run,
+    virtual_computer::CompatibilityMode,
+    RunOptions,
+};
+use clap::{Parser, Subcommand};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Filename for the ROM file to load
-    #[arg()]
-    rom_file: String,
+    #[arg(required = true)]
+    rom_file: Option<String>,
+
+    /// Where to draw the screen and read input from
+    #[arg(long, value_enum, default_value_t = Backend::Sdl)]
+    backend: Backend,
+
+    /// How many window pixels wide and tall each low resolution CHIP-8 pixel is when the window
+    /// opens. The window can be resized afterwards
+    #[arg(long, default_value_t = 14, value_parser = clap::value_parser!(u32).range(1..=64))]
+    scale: u32,
+
+    /// Opens the window fullscreen, at the desktop's resolution
+    #[arg(long)]
+    fullscreen: bool,
+
+    /// Stretches the screen to fill the window as far as its shape allows, instead of keeping
+    /// every CHIP-8 pixel a whole number of window pixels across
+    #[arg(long = "no-integer-scaling", action = clap::ArgAction::SetFalse)]
+    integer_scaling: bool,
+
+    /// Which interpreter's behavior to emulate
+    #[arg(long, value_enum, default_value_t = CompatibilityMode::CosmacVIP)]
+    profile: CompatibilityMode,
+
+    /// Overrides one of the profile's quirks, e.g. `--quirk shifting=off`. Can be repeated.
+    /// Quirks: vf-reset, memory, memory-last, display-wait, clipping, shifting, jumping, wrapping
+    #[arg(long = "quirk", value_name = "NAME=on|off")]
+    quirk_overrides: Vec<String>,
+
+    /// Where to send the buzzer's sound. Defaults to sdl, or to null with the null backend
+    #[arg(long, value_enum)]
+    audio: Option<AudioBackend>,
+
+    /// File that `--audio wav` writes to
+    #[arg(long, default_value = "chip8.wav")]
+    wav_path: PathBuf,
+
+    /// Pitch of the buzzer in hertz
+    #[arg(long, default_value_t = ToneSettings::default().frequency)]
+    tone_frequency: f32,
+
+    /// Shape of the buzzer's sound wave
+    #[arg(long, value_enum, default_value_t = ToneSettings::default().waveform)]
+    waveform: Waveform,
+
+    /// Loudness of the buzzer, from 0 to 1
+    #[arg(long, default_value_t = ToneSettings::default().volume)]
+    volume: f32,
+
+    /// How many instructions to run in each 60hz frame
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+    ipf: u32,
+
+    /// Sets the speed as a clock rate instead, rounded to a whole number of instructions per frame
+    #[arg(long, conflicts_with = "ipf")]
+    cpu_hz: Option<u32>,
+
+    /// Seed for the random number generator, to make runs reproducible
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Resumes from a save state file, such as one saved with Shift+F1-F9 in the SDL frontend
+    #[arg(long, value_name = "PATH")]
+    load_state: Option<PathBuf>,
+
+    /// Starts paused in a debugger that reads commands from stdin. Type `help` at its prompt for
+    /// the list of commands.
+    #[arg(long)]
+    debug: bool,
+
+    /// Which keys to use for the keypad: a preset (qwerty, azerty, dvorak) or a config file with
+    /// [keymap] and [gamepad] tables. Layered over ~/.config/chip8/config.toml and the ROM's own
+    /// .toml file
+    #[arg(long, value_name = "PRESET|PATH")]
+    keymap: Option<String>,
+
+    /// What to do when the program faults, e.g. by returning with an empty stack
+    #[arg(long, value_enum, default_value_t = FaultPolicy::Halt)]
+    on_fault: FaultPolicy,
+
+    /// How many nested subroutine calls fit on the stack. Defaults to the profile's: 12 for the
+    /// COSMAC VIP, 16 for everything else
+    #[arg(long, value_parser = clap::value_parser!(u16).range(1..))]
+    stack_depth: Option<u16>,
+
+    /// Colors for screenshots and recordings, which F12 and F11 take in the SDL frontend
+    #[arg(long, value_enum, default_value_t = Palette::Classic)]
+    palette: Palette,
+
+    /// How many image pixels wide and tall each CHIP-8 pixel is in screenshots and recordings
+    #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u32).range(1..=64))]
+    image_scale: u32,
+
+    /// Takes a screenshot once this many frames have run. With the null backend, the emulator
+    /// quits once it's taken, for capturing screens without a window
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    screenshot_at_frame: Option<u64>,
+
+    /// Where to write the --screenshot-at-frame screenshot. Defaults to next to the ROM, e.g.
+    /// pong.frame120.png
+    #[arg(long, value_name = "PATH", requires = "screenshot_at_frame")]
+    screenshot_path: Option<PathBuf>,
+
+    /// Records every frame to an animated GIF, or to a directory of numbered PPM images for
+    /// external encoders when PATH doesn't end in .gif
+    #[arg(long, value_name = "PATH")]
+    record: Option<PathBuf>,
+
+    /// Quits after this many frames, for headless runs of ROMs that never exit
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    max_frames: Option<u64>,
+
+    /// How many seconds of play to keep for rewinding, which is done by holding Backspace in the
+    /// SDL frontend. 0 turns rewinding off
+    #[arg(long, value_name = "SECONDS", default_value_t = 30)]
+    rewind_seconds: u32,
+
+    /// How many frames apart rewind snapshots are taken. Larger intervals use less memory but
+    /// rewind in bigger steps
+    #[arg(long, value_name = "FRAMES", default_value_t = 2,
+          value_parser = clap::value_parser!(u32).range(1..=60))]
+    rewind_interval: u32,
+
+    /// Records a movie of the run: the keypad during every frame, plus the ROM's hash, the
+    /// settings, the seed, and --on-fault, so that --play-movie can reproduce it exactly. Can't
+    /// be combined with the debugger
+    #[arg(long, value_name = "PATH", conflicts_with = "load_state")]
+    record_movie: Option<PathBuf>,
+
+    /// Replays a movie recorded with --record-movie, using its settings instead of the ones given
+    /// here. With the null backend, the emulator quits when the movie ends
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["load_state", "record_movie"])]
+    play_movie: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Prints a ROM as assembly, one instruction per line
+    Disasm {
+        /// Filename for the ROM file to disassemble
+        rom_file: PathBuf,
+
+        /// Which assembly language to print
+        #[arg(long, value_enum, default_value_t = Syntax::Cowgod)]
+        syntax: Syntax,
+
+        /// Follow jumps, calls, and skips from 0x200 instead of decoding every pair of bytes, so
+        /// that only reachable code is shown as instructions
+        #[arg(long)]
+        recursive: bool,
+    },
+
+    /// Assembles a source file into a ROM
+    Asm {
+        /// Filename for the assembly source
+        source_file: PathBuf,
+
+        /// Where to write the ROM. Defaults to the source file with a .ch8 extension.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
 
-    let rom_path = Path::new(&args.rom_file);
-
-    let file = match File::open(rom_path) {
-        Err(why) => {
-            eprintln!("Couldn't open {}: {}", rom_path.display(), why);
-            process::exit(1);
+    match &args.command {
+        Some(Command::Disasm {
+            rom_file,
+            syntax,
+            recursive,
+        }) => {
+            let rom = fs::read(rom_file)
+                .with_context(|| format!("Couldn't open {}", rom_file.display()))?;
+            let lines = if *recursive {
+                disassemble_recursive(&rom, *syntax)
+            } else {
+                disassemble(&rom, *syntax)
+            };
+            for line in lines {
+                println!("{}", line);
+            }
+            return Ok(());
+        }
+        Some(Command::Asm {
+            source_file,
+            output,
+        }) => {
+            let rom = assemble_file(source_file)?;
+            let output = output
+                .clone()
+                .unwrap_or_else(|| default_output_path(source_file));
+            fs::write(&output, rom)
+                .with_context(|| format!("Couldn't write {}", output.display()))?;
+            return Ok(());
         }
-        Ok(file) => file,
-    };
+        None => {}
+    }
+
+    let mut quirks = Quirks::from(args.profile);
+    for spec in &args.quirk_overrides {
+        quirks.apply_override(spec)?;
+    }
+
+    let rom_path = Path::new(args.rom_file.as_deref().expect("clap requires a ROM file"));
+    let keymap = Keymap::resolve(rom_path, args.keymap.as_deref())?;
 
-    run(file)?;
+    run(
+        rom_path,
+        RunOptions {
+            backend: args.backend,
+            window: WindowOptions {
+                scale: args.scale,
+                fullscreen: args.fullscreen,
+                integer_scaling: args.integer_scaling,
+            },
+            keymap,
+            compatibility_mode: args.profile,
+            quirks,
+            stack_depth: args.stack_depth.map(usize::from),
+            // Headless runs shouldn't need an audio device
+            audio: args.audio.unwrap_or(match args.backend {
+                Backend::Null => AudioBackend::Null,
+                Backend::Sdl | Backend::Terminal => AudioBackend::Sdl,
+            }),
+            wav_path: args.wav_path,
+            tone: ToneSettings {
+                frequency: args.tone_frequency,
+                waveform: args.waveform,
+                volume: args.volume.clamp(0.0, 1.0),
+            },
+            instructions_per_frame: args.cpu_hz.map_or(args.ipf, instructions_per_frame_from_hz),
+            seed: args.seed,
+            load_state: args.load_state,
+            debug: args.debug,
+            on_fault: args.on_fault,
+            palette: args.palette,
+            image_scale: args.image_scale,
+            screenshot_at_frame: args.screenshot_at_frame,
+            screenshot_path: args.screenshot_path,
+            record: args.record,
+            max_frames: args.max_frames,
+            rewind_seconds: args.rewind_seconds,
+            rewind_interval: args.rewind_interval,
+            record_movie: args.record_movie,
+            play_movie: args.play_movie,
+        },
+    )?;
     Ok(())
 }
@@ -0,0 +1,225 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    errors::FaultPolicy,
+    quirks::Quirks,
+    save_state::{StateReader, StateWriter},
+    virtual_computer::{CompatibilityMode, VirtualComputer},
+};
+
+/// Identifies a movie file, so that playing back a save state or a ROM by mistake fails cleanly.
+pub const MAGIC: &[u8; 4] = b"C8MV";
+
+/// Bumped whenever the layout changes. Movies from other versions are rejected rather than
+/// guessed at.
+pub const VERSION: u16 = 3;
+
+/// A recording of every input that went into a run, which is enough to reproduce it exactly: the
+/// emulator only depends on its settings, its seed, how faults are handled, and the keys held
+/// during each frame.
+///
+/// Movies use the same encoding as save states.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    /// SHA-256 of the ROM the movie was recorded with
+    pub rom_hash: [u8; 32],
+    pub compatibility_mode: CompatibilityMode,
+    pub quirks: Quirks,
+    pub stack_depth: usize,
+    pub seed: u64,
+    pub instructions_per_frame: u32,
+    /// Ignoring faults changes what runs afterwards, so playback has to handle them the same way
+    pub on_fault: FaultPolicy,
+    /// The keypad during each frame, one bit per key with key 0 in the lowest bit
+    pub keypads: Vec<u16>,
+}
+
+impl Movie {
+    /// Starts an empty movie of `vc`, which must have just been created from `rom` and given
+    /// `seed`.
+    pub fn new(
+        rom: &[u8],
+        vc: &VirtualComputer,
+        seed: u64,
+        instructions_per_frame: u32,
+        on_fault: FaultPolicy,
+    ) -> Self {
+        Self {
+            rom_hash: Sha256::digest(rom).into(),
+            compatibility_mode: vc.compatibility_mode(),
+            quirks: vc.quirks(),
+            stack_depth: vc.stack_depth(),
+            seed,
+            instructions_per_frame,
+            on_fault,
+            keypads: vec![],
+        }
+    }
+
+    /// Creates a computer in exactly the state the movie started from.
+    pub fn start(&self, rom: &[u8]) -> Result<VirtualComputer> {
+        if <[u8; 32]>::from(Sha256::digest(rom)) != self.rom_hash {
+            return Err(anyhow!("Movie was recorded with a different ROM"));
+        }
+
+        let mut vc = VirtualComputer::from_rom_bytes(rom, self.compatibility_mode)?;
+        vc.set_quirks(self.quirks);
+        vc.set_stack_depth(self.stack_depth);
+        vc.set_seed(self.seed);
+        Ok(vc)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = StateWriter::with_header(MAGIC, VERSION);
+
+        writer.fixed(&self.rom_hash);
+        writer.compatibility_mode(self.compatibility_mode);
+        writer.quirks(self.quirks);
+        writer.u16(self.stack_depth as u16);
+        writer.u64(self.seed);
+        writer.u32(self.instructions_per_frame);
+        writer.fault_policy(self.on_fault);
+        writer.u16s(&self.keypads);
+
+        writer.finish()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = StateReader::with_header(bytes, MAGIC, VERSION, "Movie")?;
+
+        let movie = Self {
+            rom_hash: reader.array()?,
+            compatibility_mode: reader.compatibility_mode()?,
+            quirks: reader.quirks()?,
+            stack_depth: reader.u16()? as usize,
+            seed: reader.u64()?,
+            instructions_per_frame: reader.u32()?,
+            on_fault: reader.fault_policy()?,
+            keypads: reader.u16s()?,
+        };
+        reader.finish()?;
+
+        Ok(movie)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes = fs::read(path).with_context(|| format!("Couldn't open {}", path.display()))?;
+        Self::from_bytes(&bytes).with_context(|| format!("Couldn't read {}", path.display()))
+    }
+}
+
+/// Adds a frame to a movie at a time and writes it out at the end. Runs that end in an error
+/// still write their movie, since those are the ones worth reproducing.
+pub struct MovieRecorder {
+    path: PathBuf,
+    movie: Movie,
+    written: bool,
+}
+
+impl MovieRecorder {
+    pub fn new(path: PathBuf, movie: Movie) -> Self {
+        Self {
+            path,
+            movie,
+            written: false,
+        }
+    }
+
+    /// Records the keypad for the frame that's about to run.
+    pub fn add_frame(&mut self, keypad: u16) {
+        self.movie.keypads.push(keypad);
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        self.write()
+    }
+
+    fn write(&mut self) -> Result<()> {
+        self.written = true;
+        fs::write(&self.path, self.movie.to_bytes())
+            .with_context(|| format!("Couldn't write {}", self.path.display()))
+    }
+}
+
+impl Drop for MovieRecorder {
+    fn drop(&mut self) {
+        if !self.written {
+            let _ = self.write();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// Waits for a key, then draws random bytes where the key says
+    const ROM: [u8; 12] = [
+        0xF0, 0x0A, // LD V0, K
+        0xC1, 0xFF, // RND V1, 0xFF
+        0xA3, 0x00, // LD I, 0x300
+        0xF1, 0x33, // LD B, V1
+        0xD0, 0x03, // DRW V0, V0, 3
+        0x12, 0x00, // JP 0x200
+    ];
+
+    fn run(vc: &mut VirtualComputer, keypads: &[u16], instructions_per_frame: u32) {
+        for &keypad in keypads {
+            vc.set_keypad(keypad);
+            vc.run_frame(instructions_per_frame).unwrap();
+        }
+    }
+
+    #[test]
+    fn playback_reproduces_the_recorded_run() {
+        let keypads: Vec<u16> = [0, 0, 1 << 3, 0, 0, 1 << 9, 1 << 9, 0, 0, 0]
+            .into_iter()
+            .cycle()
+            .take(200)
+            .collect();
+
+        let mut recorded =
+            VirtualComputer::from_rom_bytes(&ROM, CompatibilityMode::XoChip).unwrap();
+        recorded.set_seed(1234);
+        let mut movie = Movie::new(&ROM, &recorded, 1234, 7, FaultPolicy::Ignore);
+        movie.keypads = keypads.clone();
+        run(&mut recorded, &keypads, 7);
+
+        let movie = Movie::from_bytes(&movie.to_bytes()).unwrap();
+        let mut played_back = movie.start(&ROM).unwrap();
+        run(
+            &mut played_back,
+            &movie.keypads,
+            movie.instructions_per_frame,
+        );
+
+        assert_eq!(played_back.save_state(), recorded.save_state());
+    }
+
+    #[test]
+    fn movies_keep_their_settings_through_a_file() {
+        let vc = VirtualComputer::from_rom_bytes(&ROM, CompatibilityMode::SuperChip10).unwrap();
+        let mut movie = Movie::new(&ROM, &vc, 99, 15, FaultPolicy::Ignore);
+        movie.keypads = vec![0, 1 << 4, 0];
+
+        assert_eq!(Movie::from_bytes(&movie.to_bytes()).unwrap(), movie);
+    }
+
+    #[test]
+    fn movies_only_play_back_with_their_rom() {
+        let vc = VirtualComputer::from_rom_bytes(&ROM, CompatibilityMode::CosmacVIP).unwrap();
+        let movie = Movie::new(&ROM, &vc, 0, 10, FaultPolicy::Halt);
+
+        assert_eq!(
+            movie.start(&[0x00, 0xE0]).err().unwrap().to_string(),
+            "Movie was recorded with a different ROM"
+        );
+    }
+}
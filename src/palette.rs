@@ -0,0 +1,47 @@
+use clap::ValueEnum;
+use sdl2::pixels::Color;
+
+use crate::constants::PALETTE;
+
+/// Color schemes for exported images. Each has a color for every pixel value, in the same order
+/// as `PALETTE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Palette {
+    /// The colors the SDL and terminal frontends draw with
+    Classic,
+
+    /// Octo's default yellow on brown
+    Octo,
+
+    /// Shades of green, like the original Game Boy's screen
+    Gameboy,
+
+    /// Black, white, and two grays
+    Grayscale,
+}
+
+impl Palette {
+    pub fn colors(self) -> [Color; 4] {
+        match self {
+            Palette::Classic => *PALETTE,
+            Palette::Octo => [
+                Color::RGB(0x99, 0x66, 0x00),
+                Color::RGB(0xFF, 0xCC, 0x00),
+                Color::RGB(0xFF, 0x66, 0x00),
+                Color::RGB(0x66, 0x22, 0x00),
+            ],
+            Palette::Gameboy => [
+                Color::RGB(0x9B, 0xBC, 0x0F),
+                Color::RGB(0x0F, 0x38, 0x0F),
+                Color::RGB(0x30, 0x62, 0x30),
+                Color::RGB(0x8B, 0xAC, 0x0F),
+            ],
+            Palette::Grayscale => [
+                Color::RGB(0x00, 0x00, 0x00),
+                Color::RGB(0xFF, 0xFF, 0xFF),
+                Color::RGB(0xAA, 0xAA, 0xAA),
+                Color::RGB(0x55, 0x55, 0x55),
+            ],
+        }
+    }
+}
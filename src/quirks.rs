@@ -0,0 +1,165 @@
+use anyhow::{anyhow, Result};
+
+use crate::virtual_computer::CompatibilityMode;
+
+/// Individual behaviors that differ between CHIP-8 interpreters. Every `CompatibilityMode` maps
+/// to a set of these, and each one can be overridden on its own. The names follow the ones
+/// reported by Timendus' quirks test ROM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8XY1, 8XY2, and 8XY3 reset VF to 0
+    pub vf_reset: bool,
+
+    /// FX55 and FX65 leave I pointing just past the last register they touched
+    pub memory_increments_index: bool,
+
+    /// With `memory_increments_index`, I is left on the last register touched instead of just
+    /// past it, as SUPER-CHIP 1.0 does
+    pub memory_index_on_last: bool,
+
+    /// DXYN waits for the next 60hz tick before drawing, so at most one sprite is drawn per frame
+    pub display_wait: bool,
+
+    /// Sprites are cut off at the edges of the screen instead of wrapping around to the other side
+    pub clip_sprites: bool,
+
+    /// 8XY6 and 8XYE shift VY into VX instead of shifting VX in place
+    pub shift_uses_vy: bool,
+
+    /// BNNN jumps to XNN + VX instead of NNN + V0
+    pub jump_uses_vx: bool,
+
+    /// Addresses past the end of memory wrap around to the start instead of faulting
+    pub wrap_memory: bool,
+}
+
+impl Quirks {
+    /// The names accepted by `set`.
+    pub const NAMES: [&'static str; 8] = [
+        "vf-reset",
+        "memory",
+        "memory-last",
+        "display-wait",
+        "clipping",
+        "shifting",
+        "jumping",
+        "wrapping",
+    ];
+
+    /// Turns the named quirk on or off.
+    pub fn set(&mut self, name: &str, enabled: bool) -> Result<()> {
+        let quirk = match name {
+            "vf-reset" => &mut self.vf_reset,
+            "memory" => &mut self.memory_increments_index,
+            "memory-last" => &mut self.memory_index_on_last,
+            "display-wait" => &mut self.display_wait,
+            "clipping" => &mut self.clip_sprites,
+            "shifting" => &mut self.shift_uses_vy,
+            "jumping" => &mut self.jump_uses_vx,
+            "wrapping" => &mut self.wrap_memory,
+            _ => {
+                return Err(anyhow!(
+                    "unknown quirk '{}', expected one of: {}",
+                    name,
+                    Quirks::NAMES.join(", ")
+                ))
+            }
+        };
+
+        *quirk = enabled;
+        Ok(())
+    }
+
+    /// Applies an override written as `name=on` or `name=off` (`true`/`false` and `1`/`0` work
+    /// too).
+    pub fn apply_override(&mut self, spec: &str) -> Result<()> {
+        let (name, value) = spec
+            .split_once('=')
+            .ok_or_else(|| anyhow!("expected a quirk override like 'name=on', got '{}'", spec))?;
+
+        let enabled = match value {
+            "on" | "true" | "1" => true,
+            "off" | "false" | "0" => false,
+            _ => return Err(anyhow!("expected 'on' or 'off' for quirk '{}'", name)),
+        };
+
+        self.set(name, enabled)
+    }
+}
+
+impl From<CompatibilityMode> for Quirks {
+    fn from(compatibility_mode: CompatibilityMode) -> Self {
+        match compatibility_mode {
+            CompatibilityMode::CosmacVIP => Quirks {
+                vf_reset: true,
+                memory_increments_index: true,
+                memory_index_on_last: false,
+                display_wait: true,
+                clip_sprites: true,
+                shift_uses_vy: true,
+                jump_uses_vx: false,
+                wrap_memory: true,
+            },
+            CompatibilityMode::SuperChip10 => Quirks {
+                vf_reset: false,
+                memory_increments_index: true,
+                memory_index_on_last: true,
+                display_wait: false,
+                clip_sprites: true,
+                shift_uses_vy: false,
+                jump_uses_vx: true,
+                wrap_memory: false,
+            },
+            CompatibilityMode::Chip48 | CompatibilityMode::SuperChip11 => Quirks {
+                vf_reset: false,
+                memory_increments_index: false,
+                memory_index_on_last: false,
+                display_wait: false,
+                clip_sprites: true,
+                shift_uses_vy: false,
+                jump_uses_vx: true,
+                wrap_memory: false,
+            },
+            CompatibilityMode::XoChip => Quirks {
+                vf_reset: false,
+                memory_increments_index: true,
+                memory_index_on_last: false,
+                display_wait: false,
+                clip_sprites: false,
+                shift_uses_vy: true,
+                jump_uses_vx: false,
+                wrap_memory: true,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("shifting=off", Quirks { shift_uses_vy: false, ..CompatibilityMode::CosmacVIP.into() })]
+    #[case("clipping=false", Quirks { clip_sprites: false, ..CompatibilityMode::CosmacVIP.into() })]
+    #[case("jumping=1", Quirks { jump_uses_vx: true, ..CompatibilityMode::CosmacVIP.into() })]
+    #[case("memory-last=on", Quirks { memory_index_on_last: true, ..CompatibilityMode::CosmacVIP.into() })]
+    #[case("wrapping=off", Quirks { wrap_memory: false, ..CompatibilityMode::CosmacVIP.into() })]
+    fn apply_override_changes_one_quirk(#[case] spec: &str, #[case] expected: Quirks) {
+        let mut quirks = Quirks::from(CompatibilityMode::CosmacVIP);
+        quirks.apply_override(spec).unwrap();
+
+        assert_eq!(quirks, expected);
+    }
+
+    #[rstest]
+    #[case("shifting")]
+    #[case("shifting=maybe")]
+    #[case("wobble=on")]
+    fn apply_override_rejects_bad_specs(#[case] spec: &str) {
+        assert!(Quirks::from(CompatibilityMode::CosmacVIP)
+            .apply_override(spec)
+            .is_err());
+    }
+}
@@ -0,0 +1,236 @@
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::{
+    constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH},
+    display::Display,
+    frame_pacer::FRAMES_PER_SECOND,
+    palette::Palette,
+};
+
+/// GIF frame delays are in hundredths of a second, and most viewers slow down anything shorter
+/// than two of them, so frames that are on screen for less time than this are dropped.
+const MIN_GIF_DELAY: u64 = 2;
+
+/// Records one frame per 60hz tick, either to an animated GIF or, for external encoders, to a
+/// directory of numbered PPM images (`ffmpeg -framerate 60 -i frames/%06d.ppm out.mp4`).
+///
+/// Every frame is the size of a low resolution screen at `scale`, so high resolution frames are
+/// drawn at half scale and the size never changes partway through.
+pub struct Recorder {
+    output: Output,
+    width: usize,
+    height: usize,
+    palette: Palette,
+    /// How many frames have been added so far
+    frames: u64,
+}
+
+enum Output {
+    Gif {
+        encoder: gif::Encoder<BufWriter<File>>,
+        /// The frame on screen that hasn't been written yet, and the tick it appeared on. Frames
+        /// are only written once it's known how long they stay up.
+        pending: Option<(Vec<u8>, u64)>,
+    },
+    Frames {
+        directory: PathBuf,
+    },
+}
+
+impl Recorder {
+    /// Starts a GIF if `path` ends in `.gif`, and a directory of frames otherwise.
+    pub fn create(path: &Path, scale: u32, palette: Palette) -> Result<Self> {
+        let width = DISPLAY_WIDTH as usize * scale as usize;
+        let height = DISPLAY_HEIGHT as usize * scale as usize;
+
+        let output = if path.extension().is_some_and(|extension| extension == "gif") {
+            let file = File::create(path)
+                .with_context(|| format!("Couldn't create {}", path.display()))?;
+            let global_palette: Vec<u8> = palette
+                .colors()
+                .iter()
+                .flat_map(|color| [color.r, color.g, color.b])
+                .collect();
+            let mut encoder = gif::Encoder::new(
+                BufWriter::new(file),
+                width as u16,
+                height as u16,
+                &global_palette,
+            )?;
+            encoder.set_repeat(gif::Repeat::Infinite)?;
+            Output::Gif {
+                encoder,
+                pending: None,
+            }
+        } else {
+            fs::create_dir_all(path)
+                .with_context(|| format!("Couldn't create {}", path.display()))?;
+            Output::Frames {
+                directory: path.to_path_buf(),
+            }
+        };
+
+        Ok(Self {
+            output,
+            width,
+            height,
+            palette,
+            frames: 0,
+        })
+    }
+
+    pub fn add_frame(&mut self, display: &Display) -> Result<()> {
+        let pixels = scale_to(display, self.width, self.height);
+        let tick = self.frames;
+        self.frames += 1;
+
+        match &mut self.output {
+            Output::Gif { encoder, pending } => match pending {
+                Some((on_screen, _)) if *on_screen == pixels => {}
+                Some((on_screen, since))
+                    if centiseconds(tick) - centiseconds(*since) < MIN_GIF_DELAY =>
+                {
+                    *on_screen = pixels;
+                }
+                _ => {
+                    if let Some((on_screen, since)) = pending.replace((pixels, tick)) {
+                        write_gif_frame(encoder, self.width, self.height, on_screen, since, tick)?;
+                    }
+                }
+            },
+            Output::Frames { directory } => {
+                let path = directory.join(format!("{:06}.ppm", tick));
+                let file = File::create(&path)
+                    .with_context(|| format!("Couldn't create {}", path.display()))?;
+                let mut writer = BufWriter::new(file);
+                write!(writer, "P6\n{} {}\n255\n", self.width, self.height)?;
+                let colors = self.palette.colors();
+                for pixel in pixels {
+                    let color = colors[pixel as usize];
+                    writer.write_all(&[color.r, color.g, color.b])?;
+                }
+                writer.flush()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes out the last frame and closes the file.
+    pub fn finish(mut self) -> Result<()> {
+        self.write_pending()
+    }
+
+    fn write_pending(&mut self) -> Result<()> {
+        if let Output::Gif { encoder, pending } = &mut self.output {
+            if let Some((on_screen, since)) = pending.take() {
+                let until = self.frames.max(since + 1);
+                write_gif_frame(encoder, self.width, self.height, on_screen, since, until)?;
+            }
+            encoder.get_mut().flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs that end in an error still leave a complete recording behind, which is usually when it's
+/// wanted most.
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let _ = self.write_pending();
+    }
+}
+
+/// When a 60hz tick happens, in the hundredths of a second that GIF delays are measured in.
+/// Delays are worked out from these rather than per frame so that rounding never adds up.
+fn centiseconds(tick: u64) -> u64 {
+    tick * 100 / FRAMES_PER_SECOND as u64
+}
+
+fn write_gif_frame(
+    encoder: &mut gif::Encoder<BufWriter<File>>,
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+    since: u64,
+    until: u64,
+) -> Result<()> {
+    let mut frame = gif::Frame::from_indexed_pixels(width as u16, height as u16, pixels, None);
+    frame.delay = (centiseconds(until) - centiseconds(since)).max(MIN_GIF_DELAY) as u16;
+    encoder
+        .write_frame(&frame)
+        .map_err(|e| anyhow!("Couldn't write GIF frame: {}", e))
+}
+
+/// Samples the framebuffer's pixel values onto a `width` x `height` grid, nearest neighbor.
+fn scale_to(display: &Display, width: usize, height: usize) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let row = &display[y * display.height() / height];
+        for x in 0..width {
+            pixels.push(row[x * display.width() / width]);
+        }
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn gif_frames_last_as_long_as_they_were_on_screen() {
+        let path = std::env::temp_dir().join(format!(
+            "chip8-{}-gif_frames_last_as_long_as_they_were_on_screen.gif",
+            std::process::id()
+        ));
+        let mut recorder = Recorder::create(&path, 1, Palette::Classic).unwrap();
+        let mut display = Display::default();
+
+        // A blank screen for a second and a lit pixel for half a second, then another pixel
+        // that flickers for a single tick, which is too short to show up, before going blank
+        for _ in 0..60 {
+            recorder.add_frame(&display).unwrap();
+        }
+        display.toggle(0, 0, 0b01);
+        for _ in 0..30 {
+            recorder.add_frame(&display).unwrap();
+        }
+        display.toggle(1, 0, 0b01);
+        recorder.add_frame(&display).unwrap();
+        display.clear(0b11);
+        for _ in 0..29 {
+            recorder.add_frame(&display).unwrap();
+        }
+        recorder.finish().unwrap();
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = options.read_info(File::open(&path).unwrap()).unwrap();
+        let mut frames = vec![];
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            frames.push((frame.delay, frame.buffer[0], frame.buffer[1]));
+        }
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(frames, vec![(100, 0, 0), (50, 1, 0), (50, 0, 0)]);
+    }
+
+    #[test]
+    fn high_resolution_frames_are_drawn_at_half_scale() {
+        let mut display = Display::default();
+        display.set_high_resolution(true);
+        display.toggle(2, 0, 0b01);
+
+        let pixels = scale_to(&display, 64, 32);
+
+        assert_eq!(pixels[..3], [0, 1, 0]);
+    }
+}
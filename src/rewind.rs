@@ -0,0 +1,190 @@
+use std::collections::VecDeque;
+
+use anyhow::Result;
+
+use crate::virtual_computer::VirtualComputer;
+
+/// Changed bytes closer together than this are stored as one span, since each span costs more
+/// than a few unchanged bytes.
+const SPAN_MERGE_GAP: usize = 8;
+
+/// Keeps the last `capacity` snapshots of the computer, taken every `interval` frames, so that
+/// play can be wound back.
+///
+/// Only the newest snapshot is kept whole. Each older one is stored as the bytes that differ from
+/// the snapshot after it, which is usually a handful of registers and a few rows of the display,
+/// so a long history costs little more than one save state.
+pub struct Rewinder {
+    capacity: usize,
+    interval: u32,
+    frames_until_snapshot: u32,
+    newest: Option<Vec<u8>>,
+    /// Oldest first
+    older: VecDeque<Delta>,
+}
+
+/// How to get from one snapshot back to the one before it: the older snapshot's length, and its
+/// bytes wherever they differ from the newer one. Snapshots get longer or shorter as the stack
+/// and display resolution change, but the large fields come first, so they still line up.
+struct Delta {
+    len: usize,
+    spans: Vec<(usize, Vec<u8>)>,
+}
+
+impl Rewinder {
+    pub fn new(capacity: usize, interval: u32) -> Self {
+        Self {
+            capacity,
+            interval,
+            frames_until_snapshot: 0,
+            newest: None,
+            older: VecDeque::new(),
+        }
+    }
+
+    /// Called once per frame, before it runs. Takes a snapshot every `interval` calls.
+    pub fn record(&mut self, vc: &VirtualComputer) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.frames_until_snapshot > 0 {
+            self.frames_until_snapshot -= 1;
+            return;
+        }
+        self.frames_until_snapshot = self.interval.saturating_sub(1);
+
+        let snapshot = vc.save_state();
+        if let Some(previous) = self.newest.replace(snapshot) {
+            let newest = self.newest.as_ref().expect("just replaced");
+            self.older.push_back(Delta::between(newest, previous));
+        }
+        while self.len() > self.capacity {
+            self.older.pop_front();
+        }
+    }
+
+    /// Steps back to the newest snapshot, removing it from the buffer. Returns `None` once the
+    /// buffer is empty.
+    pub fn rewind(&mut self) -> Option<Result<VirtualComputer>> {
+        let newest = self.newest.take()?;
+        self.newest = self.older.pop_back().map(|delta| delta.apply(&newest));
+        // Take the next snapshot straight away once play resumes
+        self.frames_until_snapshot = 0;
+        Some(VirtualComputer::load_state(&newest))
+    }
+
+    /// How many snapshots there are to rewind through.
+    pub fn len(&self) -> usize {
+        self.older.len() + self.newest.is_some() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Delta {
+    /// Stores `older` relative to `newer`.
+    fn between(newer: &[u8], older: Vec<u8>) -> Self {
+        let mut spans: Vec<(usize, Vec<u8>)> = vec![];
+        for (i, &old) in older.iter().enumerate() {
+            if newer.get(i) == Some(&old) {
+                continue;
+            }
+            match spans.last_mut() {
+                Some((start, bytes)) if i - (*start + bytes.len()) < SPAN_MERGE_GAP => {
+                    bytes.extend_from_slice(&older[*start + bytes.len()..=i]);
+                }
+                _ => spans.push((i, vec![old])),
+            }
+        }
+
+        Self {
+            len: older.len(),
+            spans,
+        }
+    }
+
+    fn apply(self, newer: &[u8]) -> Vec<u8> {
+        let mut older = newer.to_vec();
+        older.resize(self.len, 0);
+        for (start, bytes) in self.spans {
+            older[start..start + bytes.len()].copy_from_slice(&bytes);
+        }
+        older
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::virtual_computer::CompatibilityMode;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    /// Counts up in V0, then switches to high resolution, which changes the size of the state
+    const ROM: [u8; 10] = [
+        0x70, 0x01, // ADD V0, 1
+        0x30, 0x05, // SE V0, 5
+        0x12, 0x00, // JP 0x200
+        0x00, 0xFF, // HIGH
+        0x12, 0x08, // JP 0x208
+    ];
+
+    #[test]
+    fn rewinding_walks_back_through_snapshots_newest_first() {
+        let mut vc = VirtualComputer::from_rom_bytes(&ROM, CompatibilityMode::SuperChip11).unwrap();
+        let mut rewinder = Rewinder::new(100, 2);
+        let mut expected = vec![];
+
+        for frame in 0..40 {
+            rewinder.record(&vc);
+            if frame % 2 == 0 {
+                expected.push(vc.save_state());
+            }
+            vc.run_frame(1).unwrap();
+        }
+
+        let mut rewound = vec![];
+        while let Some(vc) = rewinder.rewind() {
+            rewound.push(vc.unwrap().save_state());
+        }
+        expected.reverse();
+        assert_eq!(rewound, expected);
+    }
+
+    #[test]
+    fn oldest_snapshots_are_dropped_past_capacity() {
+        let mut vc = VirtualComputer::from_rom_bytes(&ROM, CompatibilityMode::SuperChip11).unwrap();
+        let mut rewinder = Rewinder::new(3, 1);
+        let mut states = vec![];
+
+        for _ in 0..10 {
+            rewinder.record(&vc);
+            states.push(vc.save_state());
+            vc.run_frame(1).unwrap();
+        }
+
+        assert_eq!(rewinder.len(), 3);
+        let oldest = (0..3).filter_map(|_| rewinder.rewind()).last().unwrap();
+        assert_eq!(oldest.unwrap().save_state(), states[7]);
+        assert!(rewinder.is_empty());
+    }
+
+    #[rstest]
+    #[case::same_length(100)]
+    #[case::longer(103)]
+    #[case::shorter(95)]
+    fn deltas_only_store_what_changed(#[case] newer_len: usize) {
+        let newer = vec![0; newer_len];
+        let mut older = vec![0; 100];
+        older[10] = 1;
+        older[12] = 2;
+        older[90] = 3;
+
+        let delta = Delta::between(&newer, older.clone());
+
+        assert_eq!(delta.spans[0], (10, vec![1, 0, 2]));
+        assert_eq!(delta.apply(&newer), older);
+    }
+}
@@ -0,0 +1,325 @@
+use anyhow::{anyhow, Result};
+
+use crate::{
+    display::Display,
+    errors::FaultPolicy,
+    quirks::Quirks,
+    virtual_computer::{CompatibilityMode, KeyPress, KeyWait},
+};
+
+/// Identifies a save state file, so that loading a ROM or some other file by mistake fails
+/// cleanly.
+pub const MAGIC: &[u8; 4] = b"C8ST";
+
+/// Bumped whenever the layout changes. States from other versions are rejected rather than
+/// guessed at.
+pub const VERSION: u16 = 5;
+
+/// Builds up a save state. Everything is little-endian, and variable-length fields are prefixed
+/// with their length as a u32.
+pub struct StateWriter {
+    bytes: Vec<u8>,
+}
+
+impl StateWriter {
+    /// Starts a state with the magic number and version already written.
+    pub fn new() -> Self {
+        Self::with_header(MAGIC, VERSION)
+    }
+
+    /// Starts some other kind of file that uses the same encoding, such as a movie.
+    pub fn with_header(magic: &[u8; 4], version: u16) -> Self {
+        let mut writer = Self { bytes: vec![] };
+        writer.bytes.extend_from_slice(magic);
+        writer.u16(version);
+        writer
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u128(&mut self, value: u128) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Writes bytes whose length is implied by the format, without a length prefix.
+    pub fn fixed(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.bytes
+            .extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        self.fixed(bytes);
+    }
+
+    pub fn u16s(&mut self, values: &[u16]) {
+        self.bytes
+            .extend_from_slice(&(values.len() as u32).to_le_bytes());
+        for &value in values {
+            self.u16(value);
+        }
+    }
+
+    pub fn compatibility_mode(&mut self, mode: CompatibilityMode) {
+        self.u8(match mode {
+            CompatibilityMode::CosmacVIP => 0,
+            CompatibilityMode::Chip48 => 1,
+            CompatibilityMode::SuperChip10 => 2,
+            CompatibilityMode::SuperChip11 => 3,
+            CompatibilityMode::XoChip => 4,
+        });
+    }
+
+    pub fn fault_policy(&mut self, policy: FaultPolicy) {
+        self.u8(match policy {
+            FaultPolicy::Halt => 0,
+            FaultPolicy::Debug => 1,
+            FaultPolicy::Ignore => 2,
+        });
+    }
+
+    pub fn quirks(&mut self, quirks: Quirks) {
+        for flag in quirk_flags(quirks) {
+            self.bool(flag);
+        }
+    }
+
+    pub fn display(&mut self, display: &Display) {
+        self.u16(display.width() as u16);
+        self.u16(display.height() as u16);
+        self.bytes(&display.rows().flatten().copied().collect::<Vec<_>>());
+    }
+
+    pub(crate) fn key_wait(&mut self, key_wait: KeyWait) {
+        let (tag, key) = match key_wait {
+            KeyWait::Idle => (0, 0),
+            KeyWait::WaitingForPress => (1, 0),
+            KeyWait::WaitingForRelease(key) => (2, key as u8),
+            KeyWait::Released(key) => (3, key as u8),
+        };
+        self.u8(tag);
+        self.u8(key);
+    }
+}
+
+impl Default for StateWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reads back a state written by `StateWriter`, failing on anything truncated or malformed.
+pub struct StateReader<'a> {
+    bytes: &'a [u8],
+    /// What's being read, for error messages
+    kind: &'static str,
+}
+
+impl<'a> StateReader<'a> {
+    /// Checks the magic number and version, leaving the reader at the start of the state.
+    pub fn new(bytes: &'a [u8]) -> Result<Self> {
+        Self::with_header(bytes, MAGIC, VERSION, "Save state")
+    }
+
+    /// Reads some other kind of file written with `StateWriter::with_header`. `kind` names it in
+    /// error messages.
+    pub fn with_header(
+        bytes: &'a [u8],
+        magic: &[u8; 4],
+        version: u16,
+        kind: &'static str,
+    ) -> Result<Self> {
+        let mut reader = Self { bytes, kind };
+
+        if reader.fixed(magic.len())? != magic {
+            return Err(anyhow!("Not a {} file", kind.to_lowercase()));
+        }
+        let found_version = reader.u16()?;
+        if found_version != version {
+            return Err(anyhow!(
+                "{} is version {}, but only version {} is supported",
+                kind,
+                found_version,
+                version
+            ));
+        }
+
+        Ok(reader)
+    }
+
+    /// Fails if anything is left over, which means the state wasn't read the way it was written.
+    pub fn finish(self) -> Result<()> {
+        if !self.bytes.is_empty() {
+            return Err(anyhow!(
+                "{} has {} unexpected trailing bytes",
+                self.kind,
+                self.bytes.len()
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn fixed(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() < len {
+            return Err(anyhow!("{} is truncated", self.kind));
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    pub fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.fixed(N)?.try_into().expect("slice has length N"))
+    }
+
+    pub fn u8(&mut self) -> Result<u8> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            other => Err(anyhow!(
+                "Invalid boolean {} in {}",
+                other,
+                self.kind.to_lowercase()
+            )),
+        }
+    }
+
+    pub fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    pub fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    pub fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    pub fn u128(&mut self) -> Result<u128> {
+        Ok(u128::from_le_bytes(self.array()?))
+    }
+
+    pub fn bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.fixed(len)
+    }
+
+    pub fn u16s(&mut self) -> Result<Vec<u16>> {
+        let len = self.u32()? as usize;
+        (0..len).map(|_| self.u16()).collect()
+    }
+
+    pub fn compatibility_mode(&mut self) -> Result<CompatibilityMode> {
+        Ok(match self.u8()? {
+            0 => CompatibilityMode::CosmacVIP,
+            1 => CompatibilityMode::Chip48,
+            2 => CompatibilityMode::SuperChip10,
+            3 => CompatibilityMode::SuperChip11,
+            4 => CompatibilityMode::XoChip,
+            other => {
+                return Err(anyhow!(
+                    "Unknown compatibility mode {} in {}",
+                    other,
+                    self.kind.to_lowercase()
+                ))
+            }
+        })
+    }
+
+    pub fn fault_policy(&mut self) -> Result<FaultPolicy> {
+        Ok(match self.u8()? {
+            0 => FaultPolicy::Halt,
+            1 => FaultPolicy::Debug,
+            2 => FaultPolicy::Ignore,
+            other => {
+                return Err(anyhow!(
+                    "Unknown fault policy {} in {}",
+                    other,
+                    self.kind.to_lowercase()
+                ))
+            }
+        })
+    }
+
+    pub fn quirks(&mut self) -> Result<Quirks> {
+        Ok(Quirks {
+            vf_reset: self.bool()?,
+            memory_increments_index: self.bool()?,
+            memory_index_on_last: self.bool()?,
+            display_wait: self.bool()?,
+            clip_sprites: self.bool()?,
+            shift_uses_vy: self.bool()?,
+            jump_uses_vx: self.bool()?,
+            wrap_memory: self.bool()?,
+        })
+    }
+
+    pub fn display(&mut self) -> Result<Display> {
+        let width = self.u16()? as usize;
+        let height = self.u16()? as usize;
+        let pixels = self.bytes()?;
+
+        Display::from_pixels(width, height, pixels.to_vec())
+            .ok_or_else(|| anyhow!("Display in {} is the wrong size", self.kind.to_lowercase()))
+    }
+
+    pub(crate) fn key_wait(&mut self) -> Result<KeyWait> {
+        let tag = self.u8()?;
+        let key = KeyPress::try_from(self.u8()?)
+            .map_err(|key| anyhow!("Unknown key {} in {}", key, self.kind.to_lowercase()))?;
+        Ok(match tag {
+            0 => KeyWait::Idle,
+            1 => KeyWait::WaitingForPress,
+            2 => KeyWait::WaitingForRelease(key),
+            3 => KeyWait::Released(key),
+            other => {
+                return Err(anyhow!(
+                    "Unknown key wait state {} in {}",
+                    other,
+                    self.kind.to_lowercase()
+                ))
+            }
+        })
+    }
+}
+
+/// The quirks in the order they are stored, which must match `StateReader::quirks`.
+fn quirk_flags(quirks: Quirks) -> [bool; 8] {
+    [
+        quirks.vf_reset,
+        quirks.memory_increments_index,
+        quirks.memory_index_on_last,
+        quirks.display_wait,
+        quirks.clip_sprites,
+        quirks.shift_uses_vy,
+        quirks.jump_uses_vx,
+        quirks.wrap_memory,
+    ]
+}
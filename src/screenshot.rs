@@ -0,0 +1,88 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+
+use crate::{display::Display, palette::Palette};
+
+/// Writes the framebuffer as an RGB PNG, with each CHIP-8 pixel drawn as a `scale` x `scale`
+/// square so that captures stay pixel exact.
+pub fn write_png<W: Write>(
+    writer: W,
+    display: &Display,
+    scale: u32,
+    palette: Palette,
+) -> Result<()> {
+    let width = display.width() as u32 * scale;
+    let height = display.height() as u32 * scale;
+
+    let mut encoder = png::Encoder::new(writer, width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&scaled_rgb(display, scale, palette))?;
+    writer.finish()?;
+
+    Ok(())
+}
+
+pub fn save_png(path: &Path, display: &Display, scale: u32, palette: Palette) -> Result<()> {
+    let file = File::create(path).with_context(|| format!("Couldn't create {}", path.display()))?;
+    write_png(BufWriter::new(file), display, scale, palette)
+}
+
+/// Three bytes per pixel, row by row, with every CHIP-8 pixel repeated `scale` times in each
+/// direction.
+pub fn scaled_rgb(display: &Display, scale: u32, palette: Palette) -> Vec<u8> {
+    let colors = palette.colors();
+    let scale = scale as usize;
+    let mut rgb = Vec::with_capacity(display.width() * display.height() * scale * scale * 3);
+
+    for row in display.rows() {
+        let mut scaled_row = Vec::with_capacity(row.len() * scale * 3);
+        for &pixel in row {
+            let color = colors[pixel as usize];
+            for _ in 0..scale {
+                scaled_row.extend_from_slice(&[color.r, color.g, color.b]);
+            }
+        }
+        for _ in 0..scale {
+            rgb.extend_from_slice(&scaled_row);
+        }
+    }
+
+    rgb
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn pngs_decode_back_to_the_scaled_framebuffer() {
+        let mut display = Display::default();
+        display.toggle(1, 0, 0b01);
+        display.toggle(0, 1, 0b10);
+
+        let mut png_bytes = vec![];
+        write_png(&mut png_bytes, &display, 2, Palette::Grayscale).unwrap();
+
+        let decoder = png::Decoder::new(std::io::Cursor::new(png_bytes));
+        let mut reader = decoder.read_info().unwrap();
+        let mut decoded = vec![0; reader.output_buffer_size().unwrap()];
+        let info = reader.next_frame(&mut decoded).unwrap();
+
+        assert_eq!((info.width, info.height), (128, 64));
+        let pixel = |x: usize, y: usize| {
+            let i = (y * 128 + x) * 3;
+            decoded[i..i + 3].to_vec()
+        };
+        assert_eq!(pixel(0, 0), [0x00, 0x00, 0x00]);
+        assert_eq!(pixel(3, 1), [0xFF, 0xFF, 0xFF]);
+        assert_eq!(pixel(1, 3), [0xAA, 0xAA, 0xAA]);
+    }
+}
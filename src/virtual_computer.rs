@@ -1,30 +1,135 @@
 use anyhow::{anyhow, Result};
 use bitmatch::bitmatch;
-use sdl2::{
-    keyboard::Keycode,
-    rect::{Point, Rect},
-    render::WindowCanvas,
-};
-use std::{collections::HashSet, fs::File, io::Read};
+use clap::ValueEnum;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::{fs::File, io::Read};
 
 use crate::{
     constants::{
-        BACKGROUND_COLOR, DISPLAY_HEIGHT, DISPLAY_WIDTH, FONT_DATA, FONT_STARTING_MEMORY_ADDRESS,
-        PIXEL_COLOR, PIXEL_HEIGHT, PIXEL_WIDTH,
+        BIG_FONT_DATA, BIG_FONT_STARTING_MEMORY_ADDRESS, FONT_DATA, FONT_STARTING_MEMORY_ADDRESS,
+        MEMORY_SIZE, ROM_START_ADDRESS, RPL_FLAG_COUNT, STACK_DEPTH, VIP_STACK_DEPTH,
+        XO_CHIP_MEMORY_SIZE,
     },
-    instruction_parser::InstructionType,
+    display::{Display, DEFAULT_PLANES},
+    errors::Chip8Error,
+    instruction_parser::{parse_instruction, InstructionType},
+    quirks::Quirks,
+    save_state::{StateReader, StateWriter},
 };
 
-#[derive(PartialEq)]
+/// Which interpreter's semantics to follow where the historical implementations disagree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
 pub enum CompatibilityMode {
-    /// The original CHIP-8 interpreter
-    CosmicVIP,
+    /// The original CHIP-8 interpreter on the COSMAC VIP
+    #[value(name = "cosmac-vip")]
+    CosmacVIP,
+
+    /// The HP-48 calculator port
+    #[value(name = "chip-48")]
+    Chip48,
+
+    /// SUPER-CHIP 1.0 for the HP-48, which can't scroll yet and leaves I on the last register
+    /// that FX55 and FX65 touch
+    #[value(name = "schip-1.0")]
+    SuperChip10,
+
+    /// SUPER-CHIP 1.1, which most "SCHIP" ROMs target
+    #[value(name = "schip-1.1")]
+    SuperChip11,
+
+    /// Octo's XO-CHIP extension
+    #[value(name = "xo-chip")]
+    XoChip,
+}
+
+impl CompatibilityMode {
+    /// Whether the SUPER-CHIP additions (high resolution, 16x16 sprites, big font) are available.
+    pub fn supports_super_chip(self) -> bool {
+        matches!(
+            self,
+            CompatibilityMode::SuperChip10
+                | CompatibilityMode::SuperChip11
+                | CompatibilityMode::XoChip
+        )
+    }
+
+    /// Whether 00CN, 00FB, and 00FC scroll the screen. SUPER-CHIP only added these in 1.1.
+    pub fn supports_scrolling(self) -> bool {
+        matches!(
+            self,
+            CompatibilityMode::SuperChip11 | CompatibilityMode::XoChip
+        )
+    }
+
+    /// Whether the XO-CHIP additions (bitplanes, audio patterns, 16-bit addresses) are available.
+    pub fn supports_xo_chip(self) -> bool {
+        self == CompatibilityMode::XoChip
+    }
+
+    /// Whether the interpreter has `instr` at all. Opcodes from an extension are unknown to
+    /// interpreters that came before it, and fault rather than half-working.
+    pub fn supports(self, instr: InstructionType) -> bool {
+        use InstructionType::*;
+
+        match instr {
+            // DXY0 is a 16x16 sprite
+            ScrollDown(_) | ScrollRight | ScrollLeft => self.supports_scrolling(),
+            Display { n: 0, .. }
+            | Exit
+            | LowResolution
+            | HighResolution
+            | SetIndexToBigFontCharInVX(_)
+            | StoreVariableRegistersToFlagsUpToVX(_)
+            | LoadFlagsToVariableRegistersUpToVX(_) => self.supports_super_chip(),
+            StoreRegisterRange { .. }
+            | LoadRegisterRange { .. }
+            | SetIndexRegisterLong
+            | SelectPlanes(_)
+            | LoadAudioPattern
+            | SetPitchToVX(_)
+            | ScrollUp(_) => self.supports_xo_chip(),
+            _ => true,
+        }
+    }
+
+    /// How many bytes of memory programs can address.
+    pub fn memory_size(self) -> usize {
+        match self {
+            CompatibilityMode::XoChip => XO_CHIP_MEMORY_SIZE,
+            _ => MEMORY_SIZE,
+        }
+    }
+
+    /// How many nested subroutine calls the interpreter has room for.
+    pub fn stack_depth(self) -> usize {
+        match self {
+            CompatibilityMode::CosmacVIP => VIP_STACK_DEPTH,
+            _ => STACK_DEPTH,
+        }
+    }
+}
+
+/// XO-CHIP's default pitch register value, which plays the audio pattern at 4000 samples per
+/// second.
+pub const DEFAULT_PITCH: u8 = 64;
 
-    /// A newer version
-    SuperChip,
+/// What the buzzer should be playing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sound {
+    Silent,
+
+    /// The plain CHIP-8 buzzer, whose tone is up to the frontend
+    Tone,
+
+    /// An XO-CHIP audio pattern, 128 1-bit samples played on loop at a rate set by `pitch`
+    Pattern {
+        pattern: [u8; 16],
+        pitch: u8,
+    },
 }
 
-#[derive(Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub enum KeyPress {
     Key0 = 0,
     Key1 = 1,
@@ -44,33 +149,12 @@ pub enum KeyPress {
     KeyF = 15,
 }
 
-impl KeyPress {
-    pub fn from_sdl_key(key: Keycode) -> Option<Self> {
-        match key {
-            Keycode::X => Some(KeyPress::Key0),
-            Keycode::Num1 => Some(KeyPress::Key1),
-            Keycode::Num2 => Some(KeyPress::Key2),
-            Keycode::Num3 => Some(KeyPress::Key3),
-            Keycode::Q => Some(KeyPress::Key4),
-            Keycode::W => Some(KeyPress::Key5),
-            Keycode::E => Some(KeyPress::Key6),
-            Keycode::A => Some(KeyPress::Key7),
-            Keycode::S => Some(KeyPress::Key8),
-            Keycode::D => Some(KeyPress::Key9),
-            Keycode::Z => Some(KeyPress::KeyA),
-            Keycode::C => Some(KeyPress::KeyB),
-            Keycode::Num4 => Some(KeyPress::KeyC),
-            Keycode::R => Some(KeyPress::KeyD),
-            Keycode::F => Some(KeyPress::KeyE),
-            Keycode::V => Some(KeyPress::KeyF),
-            _ => None,
-        }
-    }
-}
+impl TryFrom<u8> for KeyPress {
+    type Error = u8;
 
-impl std::convert::From<u8> for KeyPress {
-    fn from(value: u8) -> Self {
-        match value {
+    /// Fails with the value itself if it isn't one of the keypad's 16 keys.
+    fn try_from(value: u8) -> Result<Self, u8> {
+        Ok(match value {
             0 => KeyPress::Key0,
             1 => KeyPress::Key1,
             2 => KeyPress::Key2,
@@ -87,75 +171,371 @@ impl std::convert::From<u8> for KeyPress {
             13 => KeyPress::KeyD,
             14 => KeyPress::KeyE,
             15 => KeyPress::KeyF,
-            _ => panic!("cannot convert {} to a KeyPress", value),
-        }
+            _ => return Err(value),
+        })
     }
 }
 
+/// How far FX0A has got in waiting for a key to be pressed and then released.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum KeyWait {
+    Idle,
+    /// Keys already held when FX0A started don't count until they're pressed again
+    WaitingForPress,
+    WaitingForRelease(KeyPress),
+    Released(KeyPress),
+}
+
 pub struct VirtualComputer {
-    memory: [u8; 4096],
-    display: [[bool; DISPLAY_WIDTH as usize]; DISPLAY_HEIGHT as usize],
+    memory: Vec<u8>,
+    display: Display,
     stack: Vec<u16>,
+    /// How many return addresses `stack` can hold before 2NNN faults
+    stack_depth: usize,
     program_counter: u16,
     index_register: u16,
     delay_timer: u8,
     sound_timer: u8,
     variable_registers: [u8; 16],
+    /// The HP-48's RPL user flags, which SUPER-CHIP programs use as persistent storage
+    rpl_flags: [u8; RPL_FLAG_COUNT],
+    /// Set once the program executes 00FD
+    exited: bool,
+    /// The bitplanes that XO-CHIP drawing, clearing, and scrolling instructions affect
+    selected_planes: u8,
+    /// XO-CHIP's 1-bit sample buffer, played while the sound timer is running
+    audio_pattern: [u8; 16],
+    /// Programs that never load a pattern get the plain buzzer instead of silence
+    audio_pattern_loaded: bool,
+    /// The playback rate of `audio_pattern`, where 64 is 4000 samples per second
+    pitch: u8,
+    /// Seeded so that runs can be reproduced exactly
+    rng: ChaCha8Rng,
     compatibility_mode: CompatibilityMode,
+    quirks: Quirks,
+    /// Whether a 60hz tick has happened since the last sprite was drawn, for `Quirks::display_wait`
+    vblank_ready: bool,
+    /// The keys currently held down, one bit per key with key 0 in the lowest bit
+    keypad: u16,
+    key_wait: KeyWait,
 }
 
 impl VirtualComputer {
-    pub fn from_rom_file(mut rom_file: File) -> Result<Self> {
+    pub fn from_rom_file(
+        mut rom_file: File,
+        compatibility_mode: CompatibilityMode,
+    ) -> Result<Self> {
         let mut memory_buf = vec![];
+        rom_file.read_to_end(&mut memory_buf)?;
 
-        let allowed_rom_size = 4096 - 0x200; // First 200 bytes reserved for the "interpreter"
-        if rom_file.metadata()?.len() > allowed_rom_size {
-            return Err(anyhow!(
-                "Rom file is greater than {} bytes!",
-                allowed_rom_size
-            ));
-        }
+        Ok(Self::from_rom_bytes(&memory_buf, compatibility_mode)?)
+    }
 
-        rom_file.read_to_end(&mut memory_buf)?;
+    /// Creates a computer with the given ROM loaded at `0x200`, ready to start executing.
+    pub fn from_rom_bytes(
+        rom: &[u8],
+        compatibility_mode: CompatibilityMode,
+    ) -> Result<Self, Chip8Error> {
+        let mut vc = VirtualComputer::new(compatibility_mode);
+        vc.load_rom(rom)?;
 
-        let mut vc = VirtualComputer::default();
+        Ok(vc)
+    }
 
-        // FIXME: this is messy but I wanted to just get something working
-        for (i, data) in memory_buf.iter().enumerate() {
-            if i < allowed_rom_size as usize {
-                vc.memory[0x200 + i] = *data;
-            }
+    /// Copies the ROM into memory at `0x200`, where programs start executing.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), Chip8Error> {
+        let start = ROM_START_ADDRESS as usize;
+        let allowed_rom_size = self.memory.len() - start; // First 200 bytes reserved for the "interpreter"
+        if rom.len() > allowed_rom_size {
+            return Err(Chip8Error::RomTooLarge {
+                size: rom.len(),
+                max_size: allowed_rom_size,
+            });
         }
 
-        Ok(vc)
+        self.memory[start..start + rom.len()].copy_from_slice(rom);
+
+        Ok(())
     }
-}
 
-impl Default for VirtualComputer {
-    fn default() -> Self {
-        let mut memory = [0; 4096];
+    /// Creates a computer with empty memory (apart from the font) that follows the semantics of
+    /// the given interpreter.
+    pub fn new(compatibility_mode: CompatibilityMode) -> Self {
+        let mut memory = vec![0; compatibility_mode.memory_size()];
 
         // Fill the font characters in memory
-        for (i, font_byte) in FONT_DATA.into_iter().enumerate() {
+        for (i, font_byte) in FONT_DATA.iter().enumerate() {
             memory[*FONT_STARTING_MEMORY_ADDRESS as usize + i] = *font_byte;
         }
+        for (i, font_byte) in BIG_FONT_DATA.iter().enumerate() {
+            memory[*BIG_FONT_STARTING_MEMORY_ADDRESS as usize + i] = *font_byte;
+        }
 
         Self {
             memory,
-            display: [[false; DISPLAY_WIDTH as usize]; DISPLAY_HEIGHT as usize],
-            stack: vec![],
-            program_counter: 0x200,
+            display: Display::default(),
+            stack: Vec::with_capacity(compatibility_mode.stack_depth()),
+            stack_depth: compatibility_mode.stack_depth(),
+            program_counter: ROM_START_ADDRESS,
             index_register: 0,
-            delay_timer: 255, // TODO: check if this is right
-            sound_timer: 255, // TODO: check if this is right
+            delay_timer: 0,
+            sound_timer: 0,
             variable_registers: [0; 16],
-            compatibility_mode: CompatibilityMode::CosmicVIP,
+            rpl_flags: [0; RPL_FLAG_COUNT],
+            exited: false,
+            selected_planes: DEFAULT_PLANES,
+            audio_pattern: [0; 16],
+            audio_pattern_loaded: false,
+            pitch: DEFAULT_PITCH,
+            rng: ChaCha8Rng::seed_from_u64(rand::random()),
+            compatibility_mode,
+            quirks: compatibility_mode.into(),
+            vblank_ready: true,
+            keypad: 0,
+            key_wait: KeyWait::Idle,
         }
     }
+
+    pub fn compatibility_mode(&self) -> CompatibilityMode {
+        self.compatibility_mode
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    /// Restarts the random number generator used by CXNN from the given seed, so that the same
+    /// inputs always produce the same run.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+    }
+
+    /// Replaces the quirks that came from the compatibility mode.
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    pub fn stack_depth(&self) -> usize {
+        self.stack_depth
+    }
+
+    /// Replaces the stack depth that came from the compatibility mode. Return addresses that no
+    /// longer fit are dropped, oldest first.
+    pub fn set_stack_depth(&mut self, stack_depth: usize) {
+        self.stack_depth = stack_depth;
+        let excess = self.stack.len().saturating_sub(stack_depth);
+        self.stack.drain(..excess);
+    }
 }
 
 impl VirtualComputer {
+    /// Snapshots everything needed to resume exactly where the program is now, including the
+    /// profile and the random number generator.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut writer = StateWriter::new();
+
+        writer.compatibility_mode(self.compatibility_mode);
+        writer.quirks(self.quirks);
+        writer.bytes(&self.memory);
+        writer.display(&self.display);
+        writer.u16(self.stack_depth as u16);
+        writer.u16s(&self.stack);
+        writer.u16(self.program_counter);
+        writer.u16(self.index_register);
+        writer.u8(self.delay_timer);
+        writer.u8(self.sound_timer);
+        writer.fixed(&self.variable_registers);
+        writer.fixed(&self.rpl_flags);
+        writer.bool(self.exited);
+        writer.u8(self.selected_planes);
+        writer.fixed(&self.audio_pattern);
+        writer.bool(self.audio_pattern_loaded);
+        writer.u8(self.pitch);
+        writer.fixed(&self.rng.get_seed());
+        writer.u64(self.rng.get_stream());
+        writer.u128(self.rng.get_word_pos());
+        writer.bool(self.vblank_ready);
+        writer.u16(self.keypad);
+        writer.key_wait(self.key_wait);
+
+        writer.finish()
+    }
+
+    /// Recreates a computer from a snapshot taken by `save_state`.
+    pub fn load_state(state: &[u8]) -> Result<Self> {
+        let mut reader = StateReader::new(state)?;
+
+        let compatibility_mode = reader.compatibility_mode()?;
+        let quirks = reader.quirks()?;
+        let memory = reader.bytes()?.to_vec();
+        if memory.len() != compatibility_mode.memory_size() {
+            return Err(anyhow!(
+                "Save state has {} bytes of memory, but {:?} needs {}",
+                memory.len(),
+                compatibility_mode,
+                compatibility_mode.memory_size()
+            ));
+        }
+        let display = reader.display()?;
+        let stack_depth = reader.u16()? as usize;
+        let stack = reader.u16s()?;
+        if stack.len() > stack_depth {
+            return Err(anyhow!(
+                "Save state has {} return addresses on a stack that only holds {}",
+                stack.len(),
+                stack_depth
+            ));
+        }
+        let program_counter = reader.u16()?;
+        let index_register = reader.u16()?;
+        let delay_timer = reader.u8()?;
+        let sound_timer = reader.u8()?;
+        let variable_registers = reader.array()?;
+        let rpl_flags = reader.array()?;
+        let exited = reader.bool()?;
+        let selected_planes = reader.u8()?;
+        let audio_pattern = reader.array()?;
+        let audio_pattern_loaded = reader.bool()?;
+        let pitch = reader.u8()?;
+        let mut rng = ChaCha8Rng::from_seed(reader.array()?);
+        rng.set_stream(reader.u64()?);
+        rng.set_word_pos(reader.u128()?);
+        let vblank_ready = reader.bool()?;
+        let keypad = reader.u16()?;
+        let key_wait = reader.key_wait()?;
+        reader.finish()?;
+
+        Ok(Self {
+            memory,
+            display,
+            stack,
+            stack_depth,
+            program_counter,
+            index_register,
+            delay_timer,
+            sound_timer,
+            variable_registers,
+            rpl_flags,
+            exited,
+            selected_planes,
+            audio_pattern,
+            audio_pattern_loaded,
+            pitch,
+            rng,
+            compatibility_mode,
+            quirks,
+            vblank_ready,
+            keypad,
+            key_wait,
+        })
+    }
+}
+
+impl Default for VirtualComputer {
+    fn default() -> Self {
+        Self::new(CompatibilityMode::CosmacVIP)
+    }
+}
+
+impl VirtualComputer {
+    /// The current contents of the screen. Frontends read this after executing instructions to
+    /// decide what to draw.
+    pub fn display(&self) -> &Display {
+        &self.display
+    }
+
+    /// What the buzzer should be playing right now.
+    pub fn sound(&self) -> Sound {
+        if self.sound_timer == 0 {
+            Sound::Silent
+        } else if self.audio_pattern_loaded {
+            Sound::Pattern {
+                pattern: self.audio_pattern,
+                pitch: self.pitch,
+            }
+        } else {
+            Sound::Tone
+        }
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn program_counter(&self) -> u16 {
+        self.program_counter
+    }
+
+    pub fn index_register(&self) -> u16 {
+        self.index_register
+    }
+
+    /// V0 through VF.
+    pub fn variable_registers(&self) -> &[u8; 16] {
+        &self.variable_registers
+    }
+
+    /// Return addresses, with the most recent call last.
+    pub fn stack(&self) -> &[u16] {
+        &self.stack
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    /// The keys currently held down, one bit per key with key 0 in the lowest bit.
+    pub fn keypad(&self) -> u16 {
+        self.keypad
+    }
+
+    pub fn is_key_down(&self, key: KeyPress) -> bool {
+        self.keypad & (1 << key as u16) != 0
+    }
+
+    /// Presses `key`. Pressing a key that's already down does nothing, so that the host's key
+    /// repeat can't count as a fresh press.
+    pub fn press_key(&mut self, key: KeyPress) {
+        if self.is_key_down(key) {
+            return;
+        }
+        self.keypad |= 1 << key as u16;
+        if self.key_wait == KeyWait::WaitingForPress {
+            self.key_wait = KeyWait::WaitingForRelease(key);
+        }
+    }
+
+    pub fn release_key(&mut self, key: KeyPress) {
+        self.keypad &= !(1 << key as u16);
+        if self.key_wait == KeyWait::WaitingForRelease(key) {
+            self.key_wait = KeyWait::Released(key);
+        }
+    }
+
+    /// Presses and releases keys until exactly the ones in `keypad` are held down.
+    pub fn set_keypad(&mut self, keypad: u16) {
+        for i in 0..16 {
+            let key = KeyPress::try_from(i).expect("keys 0-15 exist");
+            match (self.is_key_down(key), keypad & (1 << i) != 0) {
+                (false, true) => self.press_key(key),
+                (true, false) => self.release_key(key),
+                _ => {}
+            }
+        }
+    }
+
+    /// Whether the program has asked the interpreter to quit with 00FD.
+    pub fn has_exited(&self) -> bool {
+        self.exited
+    }
+
     pub fn decrement_timers(&mut self) {
+        self.vblank_ready = true;
+
         if self.delay_timer > 0 {
             self.delay_timer -= 1;
         }
@@ -165,58 +545,97 @@ impl VirtualComputer {
         }
     }
 
-    pub fn fetch_instruction_and_increment_pc(&mut self) -> Option<u16> {
-        if self.program_counter >= 4096 {
-            return None;
+    /// Runs one 60hz frame: executes `instructions_per_frame` instructions (fewer if the program
+    /// exits), then ticks the timers once. The result only depends on the computer's state and
+    /// the keys pressed, never on how long the frame took on the host. A fault ends the frame
+    /// straight away, without ticking the timers.
+    pub fn run_frame(&mut self, instructions_per_frame: u32) -> Result<(), Chip8Error> {
+        for _ in 0..instructions_per_frame {
+            if self.exited {
+                break;
+            }
+            self.step()?;
         }
 
-        let instr = ((self.memory[self.program_counter as usize] as u16) << 8)
-            | self.memory[self.program_counter as usize + 1] as u16;
-        self.program_counter = self.program_counter + 2;
-        Some(instr)
+        self.decrement_timers();
+        Ok(())
     }
 
-    #[bitmatch]
-    pub fn execute_instruction(
-        &mut self,
-        instr: InstructionType,
-        canvas: &mut WindowCanvas,
-        keys_pressed: &HashSet<KeyPress>,
-    ) {
-        println!("Executing instruction: {:?}", instr);
+    /// Moves the program counter past the instruction it points at, so that execution can carry
+    /// on after a fault as though the instruction were never there.
+    pub fn skip_faulting_instruction(&mut self) {
+        self.skip_next_instruction();
+    }
+
+    /// Fetches and executes a single instruction. Does nothing once the program has exited. If
+    /// the instruction faults, the program counter is left pointing at it.
+    pub fn step(&mut self) -> Result<(), Chip8Error> {
+        if self.exited {
+            return Ok(());
+        }
+
+        let address = self.program_counter;
+        let result = self
+            .fetch_instruction_and_increment_pc()
+            .and_then(|opcode| {
+                let instr = parse_instruction(opcode)
+                    .filter(|&instr| self.compatibility_mode.supports(instr))
+                    .ok_or(Chip8Error::UnknownOpcode { opcode, address })?;
+                self.execute_instruction(instr)
+            });
+
+        if result.is_err() {
+            self.program_counter = address;
+        }
+        result
+    }
+
+    pub fn fetch_instruction_and_increment_pc(&mut self) -> Result<u16, Chip8Error> {
+        let pc = self.program_counter as usize;
+        let instr = u16::from_be_bytes([self.read_memory(pc)?, self.read_memory(pc + 1)?]);
+        self.program_counter = self.program_counter.wrapping_add(2);
+        Ok(instr)
+    }
 
+    #[bitmatch]
+    pub fn execute_instruction(&mut self, instr: InstructionType) -> Result<(), Chip8Error> {
         match instr {
             InstructionType::ClearScreen => {
-                canvas.set_draw_color(*BACKGROUND_COLOR);
-                canvas.clear();
+                self.display.clear(self.selected_planes);
             }
             InstructionType::JumpToMemoryLocation(nnn) => self.program_counter = nnn,
             InstructionType::CallSubroutine(nnn) => {
+                if self.stack.len() >= self.stack_depth {
+                    return Err(Chip8Error::StackOverflow {
+                        address: self.instruction_address(),
+                    });
+                }
                 self.stack.push(self.program_counter);
                 self.program_counter = nnn;
             }
-            InstructionType::ReturnFromSubroutine => match self.stack.pop() {
-                Some(pc) => self.program_counter = pc,
-                None => eprintln!("Attempted to return from a subroutine with an empty stack"),
-            },
+            InstructionType::ReturnFromSubroutine => {
+                self.program_counter = self.stack.pop().ok_or(Chip8Error::StackUnderflow {
+                    address: self.instruction_address(),
+                })?;
+            }
             InstructionType::SkipIfRegisterEqValue { vx, value } => {
                 if self.variable_registers[vx as usize] == value {
-                    self.program_counter += 2;
+                    self.skip_next_instruction();
                 }
             }
             InstructionType::SkipIfRegisterNeqValue { vx, value } => {
                 if self.variable_registers[vx as usize] != value {
-                    self.program_counter += 2;
+                    self.skip_next_instruction();
                 }
             }
             InstructionType::SkipIfRegistersEq { vx, vy } => {
                 if self.variable_registers[vx as usize] == self.variable_registers[vy as usize] {
-                    self.program_counter += 2;
+                    self.skip_next_instruction();
                 }
             }
             InstructionType::SkipIfRegistersNeq { vx, vy } => {
                 if self.variable_registers[vx as usize] != self.variable_registers[vy as usize] {
-                    self.program_counter += 2;
+                    self.skip_next_instruction();
                 }
             }
             InstructionType::UpdateRegister { vx, value } => {
@@ -231,12 +650,15 @@ impl VirtualComputer {
             }
             InstructionType::BitwiseOR { vx, vy } => {
                 self.variable_registers[vx as usize] |= self.variable_registers[vy as usize];
+                self.reset_flag_after_logic_op();
             }
             InstructionType::BitwiseAND { vx, vy } => {
                 self.variable_registers[vx as usize] &= self.variable_registers[vy as usize];
+                self.reset_flag_after_logic_op();
             }
             InstructionType::BitwiseXOR { vx, vy } => {
                 self.variable_registers[vx as usize] ^= self.variable_registers[vy as usize];
+                self.reset_flag_after_logic_op();
             }
             InstructionType::AddRegisterToRegister { vx, vy } => {
                 match self.variable_registers[vx as usize]
@@ -277,106 +699,102 @@ impl VirtualComputer {
                 self.variable_registers[vx as usize] = minuend.wrapping_sub(subtrahend);
             }
             InstructionType::ShiftLeft { vx, vy } => {
-                let x = self.variable_registers[vy as usize];
-
-                self.variable_registers[vx as usize] = (((x as u16) << 1) & 0xFF) as u8;
+                let x = self.shift_source(vx, vy);
 
-                if self.compatibility_mode == CompatibilityMode::CosmicVIP {
-                    self.variable_registers[0xF] = (x & 0x40) >> 7;
-                }
+                self.variable_registers[vx as usize] = x << 1;
+                self.variable_registers[0xF] = x >> 7;
             }
             InstructionType::ShiftRight { vx, vy } => {
-                let x = self.variable_registers[vy as usize];
+                let x = self.shift_source(vx, vy);
 
                 self.variable_registers[vx as usize] = x >> 1;
-
-                if self.compatibility_mode == CompatibilityMode::CosmicVIP {
-                    self.variable_registers[0xF] = x & 1;
-                }
+                self.variable_registers[0xF] = x & 1;
             }
             InstructionType::SetIndexRegister(nnn) => self.index_register = nnn,
-            InstructionType::JumpWithOffset(nnn) => match self.compatibility_mode {
-                CompatibilityMode::CosmicVIP => {
-                    self.program_counter = nnn + self.variable_registers[0] as u16;
-                }
-                CompatibilityMode::SuperChip => {
+            InstructionType::JumpWithOffset(nnn) => {
+                if self.quirks.jump_uses_vx {
                     #[bitmatch]
                     let "????xxxx????????" = nnn;
 
                     self.program_counter = nnn + self.variable_registers[x as usize] as u16;
+                } else {
+                    self.program_counter = nnn + self.variable_registers[0] as u16;
                 }
-            },
+            }
             InstructionType::GenerateRandomNumber { vx, bitmask } => {
-                self.variable_registers[vx as usize] = rand::random::<u8>() & bitmask;
+                self.variable_registers[vx as usize] = self.rng.gen::<u8>() & bitmask;
             }
             InstructionType::Display { vx, vy, n } => {
-                let x = self.variable_registers[vx as usize] % DISPLAY_WIDTH;
-                let y = self.variable_registers[vy as usize] % DISPLAY_HEIGHT;
+                if self.quirks.display_wait {
+                    if !self.vblank_ready {
+                        // Try again once the next frame starts
+                        self.program_counter = self.instruction_address();
+                        return Ok(());
+                    }
+                    self.vblank_ready = false;
+                }
+
+                let width = self.display.width();
+                let height = self.display.height();
+                let x = self.variable_registers[vx as usize] as usize % width;
+                let y = self.variable_registers[vy as usize] as usize % height;
+
+                // SUPER-CHIP draws a 16x16 sprite for DXY0, stored as two bytes per row
+                let (sprite_width, sprite_height) = if n == 0 { (16, 16) } else { (8, n as usize) };
+                let bytes_per_row = sprite_width / 8;
+                let sprite_size = bytes_per_row * sprite_height;
 
                 self.variable_registers[0xF] = 0;
-                let mut was_toggled_off = false;
-
-                for i in 0..n {
-                    let sprite_data = self.memory[(self.index_register + i as u16) as usize];
-
-                    let py = y + i;
-
-                    for j in 0..8 {
-                        let px = x + j;
-
-                        if px < DISPLAY_WIDTH && py < DISPLAY_HEIGHT {
-                            let pixel_bit = (sprite_data >> (7 - j)) & 1;
-
-                            if pixel_bit == 1 {
-                                // Flip the display pixel
-
-                                if !was_toggled_off && self.display[py as usize][px as usize] {
-                                    was_toggled_off = true;
-                                    self.variable_registers[0xF] = 1;
-                                }
-
-                                if self.display[py as usize][px as usize] {
-                                    canvas.set_draw_color(*BACKGROUND_COLOR);
-                                } else {
-                                    canvas.set_draw_color(*PIXEL_COLOR);
-                                }
-                                canvas
-                                    .fill_rect(Rect::new(
-                                        (px as u32 * PIXEL_WIDTH as u32) as i32,
-                                        (py as u32 * PIXEL_HEIGHT as u32) as i32,
-                                        PIXEL_WIDTH as u32,
-                                        PIXEL_HEIGHT as u32,
-                                    ))
-                                    .unwrap();
-                                // canvas.draw_point(Point::new(px as i32, py as i32)).unwrap();
-
-                                self.display[py as usize][px as usize] =
-                                    !self.display[py as usize][px as usize];
+
+                // XO-CHIP draws the sprite once per selected plane, with each plane's data
+                // following the previous one's in memory
+                let selected_planes = [0b01, 0b10]
+                    .into_iter()
+                    .filter(|plane| self.selected_planes & plane != 0);
+
+                for (plane_index, plane) in selected_planes.enumerate() {
+                    let sprite_address = self.index_register as usize + plane_index * sprite_size;
+
+                    for i in 0..sprite_height {
+                        let py = y + i;
+                        if py >= height && self.quirks.clip_sprites {
+                            break;
+                        }
+                        let py = py % height;
+
+                        let row_address = sprite_address + i * bytes_per_row;
+                        let mut sprite_data = 0u16;
+                        for byte in 0..bytes_per_row {
+                            sprite_data =
+                                (sprite_data << 8) | self.read_memory(row_address + byte)? as u16;
+                        }
+
+                        for j in 0..sprite_width {
+                            let px = x + j;
+                            if px >= width && self.quirks.clip_sprites {
+                                break;
+                            }
+                            let px = px % width;
+
+                            let pixel_bit = (sprite_data >> (sprite_width - 1 - j)) & 1;
+
+                            if pixel_bit == 1 && self.display.toggle(px, py, plane) {
+                                self.variable_registers[0xF] = 1;
                             }
                         }
                     }
                 }
             }
             InstructionType::SkipIfPressedVX(vx) => {
-                let x = self.variable_registers[vx as usize];
-                if x > 15 {
-                    eprintln!("Key {} is out of the range [0, 15]", x);
-                    return;
-                }
-
-                if keys_pressed.contains(&KeyPress::from(x)) {
-                    self.program_counter += 2;
+                let key = self.key_in_register(vx)?;
+                if self.is_key_down(key) {
+                    self.skip_next_instruction();
                 }
             }
             InstructionType::SkipIfNotPressedVX(vx) => {
-                let x = self.variable_registers[vx as usize];
-                if x > 15 {
-                    eprintln!("Key {} is out of the range [0, 15]", x);
-                    return;
-                }
-
-                if !keys_pressed.contains(&KeyPress::from(x)) {
-                    self.program_counter += 2;
+                let key = self.key_in_register(vx)?;
+                if !self.is_key_down(key) {
+                    self.skip_next_instruction();
                 }
             }
             InstructionType::FetchDelayTimerToVX(vx) => {
@@ -399,17 +817,21 @@ impl VirtualComputer {
                     self.variable_registers[0xF] = 1;
                 }
             }
-            InstructionType::WaitForKeyInVX(vx) => {
-                let x = self.variable_registers[vx as usize];
-                if x > 15 {
-                    eprintln!("Key {} is out of the range [0, 15]", x);
-                    return;
+            // Blocks by running itself again until a key has been pressed and released, so the
+            // timers keep ticking while it waits
+            InstructionType::WaitForKeyInVX(vx) => match self.key_wait {
+                KeyWait::Released(key) => {
+                    self.variable_registers[vx as usize] = key as u8;
+                    self.key_wait = KeyWait::Idle;
                 }
-
-                if !keys_pressed.contains(&KeyPress::from(x)) {
-                    self.program_counter -= 2;
+                KeyWait::Idle => {
+                    self.key_wait = KeyWait::WaitingForPress;
+                    self.program_counter = self.instruction_address();
                 }
-            }
+                KeyWait::WaitingForPress | KeyWait::WaitingForRelease(_) => {
+                    self.program_counter = self.instruction_address();
+                }
+            },
             InstructionType::SetIndexToFontCharInVX(vx) => {
                 let x = 0xF & self.variable_registers[vx as usize];
 
@@ -418,31 +840,689 @@ impl VirtualComputer {
             }
             InstructionType::BinaryCodedDecimalConversionForVX(vx) => {
                 let x = self.variable_registers[vx as usize];
-                self.memory[self.index_register as usize] = (x as f64 / 100.0).floor() as u8;
-                self.memory[(self.index_register + 1) as usize] =
-                    ((x % 100) as f64 / 10.0).floor() as u8;
-                self.memory[(self.index_register + 2) as usize] = x % 10;
+                let i = self.index_register as usize;
+                self.write_memory(i, x / 100)?;
+                self.write_memory(i + 1, x % 100 / 10)?;
+                self.write_memory(i + 2, x % 10)?;
             }
             InstructionType::StoreVariableRegistersToMemoryUpToVX(vx) => {
-                for i in 0..=vx {
-                    self.memory[(self.index_register + i as u16) as usize] =
-                        self.variable_registers[i as usize];
+                for i in 0..=vx as usize {
+                    self.write_memory(
+                        self.index_register as usize + i,
+                        self.variable_registers[i],
+                    )?;
                 }
 
-                if self.compatibility_mode == CompatibilityMode::CosmicVIP {
-                    self.index_register += vx as u16;
-                }
+                self.increment_index_after_load_store(vx);
             }
             InstructionType::LoadMemoryToVariableRegistersFromVXAddress(vx) => {
-                for i in 0..=vx {
-                    self.variable_registers[i as usize] =
-                        self.memory[(self.index_register + i as u16) as usize];
+                for i in 0..=vx as usize {
+                    self.variable_registers[i] =
+                        self.read_memory(self.index_register as usize + i)?;
                 }
 
-                if self.compatibility_mode == CompatibilityMode::CosmicVIP {
-                    self.index_register += vx as u16;
+                self.increment_index_after_load_store(vx);
+            }
+            InstructionType::ScrollDown(n) => {
+                self.display.scroll_down(n as usize, self.selected_planes)
+            }
+            InstructionType::ScrollRight => self.display.scroll_right(4, self.selected_planes),
+            InstructionType::ScrollLeft => self.display.scroll_left(4, self.selected_planes),
+            InstructionType::Exit => self.exited = true,
+            InstructionType::LowResolution => self.display.set_high_resolution(false),
+            InstructionType::HighResolution => self.display.set_high_resolution(true),
+            InstructionType::SetIndexToBigFontCharInVX(vx) => {
+                let x = 0xF & self.variable_registers[vx as usize];
+
+                // Big characters are 10 bytes
+                self.index_register = *BIG_FONT_STARTING_MEMORY_ADDRESS + x as u16 * 10;
+            }
+            InstructionType::StoreVariableRegistersToFlagsUpToVX(vx) => {
+                let count = vx as usize + 1;
+                self.rpl_flags[..count].copy_from_slice(&self.variable_registers[..count]);
+            }
+            InstructionType::LoadFlagsToVariableRegistersUpToVX(vx) => {
+                let count = vx as usize + 1;
+                self.variable_registers[..count].copy_from_slice(&self.rpl_flags[..count]);
+            }
+            InstructionType::StoreRegisterRange { vx, vy } => {
+                for (offset, register) in register_range(vx, vy).enumerate() {
+                    self.write_memory(
+                        self.index_register as usize + offset,
+                        self.variable_registers[register as usize],
+                    )?;
+                }
+            }
+            InstructionType::LoadRegisterRange { vx, vy } => {
+                for (offset, register) in register_range(vx, vy).enumerate() {
+                    self.variable_registers[register as usize] =
+                        self.read_memory(self.index_register as usize + offset)?;
+                }
+            }
+            InstructionType::SetIndexRegisterLong => {
+                // The address is stored in the word following the instruction
+                let pc = self.program_counter as usize;
+                self.index_register =
+                    u16::from_be_bytes([self.read_memory(pc)?, self.read_memory(pc + 1)?]);
+                self.program_counter = self.program_counter.wrapping_add(2);
+            }
+            InstructionType::SelectPlanes(n) => self.selected_planes = n & 0b11,
+            InstructionType::LoadAudioPattern => {
+                for i in 0..self.audio_pattern.len() {
+                    self.audio_pattern[i] = self.read_memory(self.index_register as usize + i)?;
                 }
+                self.audio_pattern_loaded = true;
+            }
+            InstructionType::SetPitchToVX(vx) => self.pitch = self.variable_registers[vx as usize],
+            InstructionType::ScrollUp(n) => {
+                self.display.scroll_up(n as usize, self.selected_planes)
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl VirtualComputer {
+    /// Which register 8XY6 and 8XYE read from, per `Quirks::shift_uses_vy`.
+    fn shift_source(&self, vx: u8, vy: u8) -> u8 {
+        if self.quirks.shift_uses_vy {
+            self.variable_registers[vy as usize]
+        } else {
+            self.variable_registers[vx as usize]
+        }
+    }
+
+    fn increment_index_after_load_store(&mut self, vx: u8) {
+        if self.quirks.memory_increments_index {
+            let touched = if self.quirks.memory_index_on_last {
+                vx as u16
+            } else {
+                vx as u16 + 1
+            };
+            self.index_register = self.index_register.wrapping_add(touched);
+        }
+    }
+
+    /// Skips over the next instruction, which is two words long if it is XO-CHIP's F000 NNNN.
+    fn skip_next_instruction(&mut self) {
+        let next_is_long = self.compatibility_mode.supports_xo_chip()
+            && self.memory.get(self.program_counter as usize) == Some(&0xF0)
+            && self.memory.get(self.program_counter as usize + 1) == Some(&0x00);
+
+        let length = if next_is_long { 4 } else { 2 };
+        self.program_counter = self.program_counter.wrapping_add(length);
+    }
+
+    /// The address of the instruction being executed, since the program counter has already
+    /// moved past it.
+    fn instruction_address(&self) -> u16 {
+        self.program_counter.wrapping_sub(2)
+    }
+
+    fn key_in_register(&self, vx: u8) -> Result<KeyPress, Chip8Error> {
+        KeyPress::try_from(self.variable_registers[vx as usize]).map_err(|key| {
+            Chip8Error::InvalidKey {
+                key,
+                address: self.instruction_address(),
             }
+        })
+    }
+
+    /// Every memory access goes through here, so that addresses past the end of memory either
+    /// wrap or fault according to `Quirks::wrap_memory`, and never panic.
+    fn resolve_address(&self, address: usize) -> Result<usize, Chip8Error> {
+        if address < self.memory.len() {
+            Ok(address)
+        } else if self.quirks.wrap_memory {
+            Ok(address % self.memory.len())
+        } else {
+            Err(Chip8Error::MemoryOutOfBounds { address })
         }
     }
+
+    fn read_memory(&self, address: usize) -> Result<u8, Chip8Error> {
+        Ok(self.memory[self.resolve_address(address)?])
+    }
+
+    fn write_memory(&mut self, address: usize, value: u8) -> Result<(), Chip8Error> {
+        let address = self.resolve_address(address)?;
+        self.memory[address] = value;
+        Ok(())
+    }
+
+    fn reset_flag_after_logic_op(&mut self) {
+        if self.quirks.vf_reset {
+            self.variable_registers[0xF] = 0;
+        }
+    }
+}
+
+/// The registers from VX to VY, in descending order if X is larger.
+fn register_range(vx: u8, vy: u8) -> Box<dyn Iterator<Item = u8>> {
+    if vx <= vy {
+        Box::new(vx..=vy)
+    } else {
+        Box::new((vy..=vx).rev())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    /// Loads the given instructions as a ROM and executes them all in order, one per frame.
+    fn run_program(program: &[u16], compatibility_mode: CompatibilityMode) -> VirtualComputer {
+        let rom: Vec<u8> = program
+            .iter()
+            .flat_map(|instr| instr.to_be_bytes())
+            .collect();
+        let mut vc = VirtualComputer::from_rom_bytes(&rom, compatibility_mode).unwrap();
+
+        for _ in program {
+            step(&mut vc);
+            vc.decrement_timers();
+        }
+
+        vc
+    }
+
+    fn step(vc: &mut VirtualComputer) {
+        vc.step().unwrap();
+    }
+
+    #[test]
+    fn display_draws_sprite_into_framebuffer() {
+        // Draw the "0" font character (0xF0 on its first row) at (0, 0)
+        let vc = run_program(&[0x6000, 0xF029, 0xD005], CompatibilityMode::CosmacVIP);
+
+        assert_eq!(vc.display()[0][..5], [1, 1, 1, 1, 0]);
+        assert_eq!(vc.display()[1][..5], [1, 0, 0, 1, 0]);
+        assert_eq!(vc.variable_registers[0xF], 0);
+    }
+
+    #[test]
+    fn display_xors_pixels_and_reports_collision() {
+        let vc = run_program(
+            &[0x6000, 0xF029, 0xD005, 0xD005],
+            CompatibilityMode::CosmacVIP,
+        );
+
+        assert!(vc.display().rows().flatten().all(|&pixel| pixel == 0));
+        assert_eq!(vc.variable_registers[0xF], 1);
+    }
+
+    #[test]
+    fn clear_screen_resets_framebuffer() {
+        let vc = run_program(
+            &[0x6000, 0xF029, 0xD005, 0x00E0],
+            CompatibilityMode::CosmacVIP,
+        );
+
+        assert!(vc.display().rows().flatten().all(|&pixel| pixel == 0));
+    }
+
+    #[rstest]
+    #[case(CompatibilityMode::CosmacVIP, 0x0F, 0)]
+    #[case(CompatibilityMode::Chip48, 0x41, 1)]
+    #[case(CompatibilityMode::SuperChip11, 0x41, 1)]
+    #[case(CompatibilityMode::XoChip, 0x0F, 0)]
+    fn shift_right_source_depends_on_profile(
+        #[case] compatibility_mode: CompatibilityMode,
+        #[case] expected: u8,
+        #[case] expected_flag: u8,
+    ) {
+        // V0 = 0x83, V1 = 0x1E, V0 >>= V1
+        let vc = run_program(&[0x6083, 0x611E, 0x8016], compatibility_mode);
+
+        assert_eq!(vc.variable_registers[0], expected);
+        assert_eq!(vc.variable_registers[0xF], expected_flag);
+    }
+
+    #[rstest]
+    #[case(CompatibilityMode::CosmacVIP, 0x303)]
+    #[case(CompatibilityMode::Chip48, 0x300)]
+    #[case(CompatibilityMode::SuperChip10, 0x302)]
+    #[case(CompatibilityMode::SuperChip11, 0x300)]
+    #[case(CompatibilityMode::XoChip, 0x303)]
+    fn store_registers_increments_index_per_profile(
+        #[case] compatibility_mode: CompatibilityMode,
+        #[case] expected: u16,
+    ) {
+        let vc = run_program(&[0xA300, 0xF255], compatibility_mode);
+
+        assert_eq!(vc.index_register, expected);
+    }
+
+    #[rstest]
+    #[case::store(0xF255)]
+    #[case::load(0xF265)]
+    fn super_chip_1_0_leaves_index_on_the_last_register(#[case] opcode: u16) {
+        let schip_1_0 = run_program(&[0xA300, opcode], CompatibilityMode::SuperChip10);
+        let schip_1_1 = run_program(&[0xA300, opcode], CompatibilityMode::SuperChip11);
+
+        assert_eq!(schip_1_0.index_register, 0x302);
+        assert_eq!(schip_1_1.index_register, 0x300);
+    }
+
+    #[test]
+    fn display_wait_stalls_second_draw_until_next_frame() {
+        let rom = [0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05, 0xD0, 0x05];
+        let mut vc = VirtualComputer::from_rom_bytes(&rom, CompatibilityMode::CosmacVIP).unwrap();
+
+        for _ in 0..4 {
+            step(&mut vc);
+        }
+        assert_eq!(vc.program_counter, 0x206);
+
+        vc.decrement_timers();
+        step(&mut vc);
+        assert_eq!(vc.program_counter, 0x208);
+    }
+
+    #[rstest]
+    #[case(true, [0, 0, 0])]
+    #[case(false, [1, 1, 0])]
+    fn sprites_wrap_unless_clipped(#[case] clip_sprites: bool, #[case] expected: [u8; 3]) {
+        // Draw the "0" font character at (62, 0)
+        let rom = [0x60, 0x3E, 0x61, 0x00, 0xF0, 0x29, 0xD0, 0x15];
+        let mut vc = VirtualComputer::from_rom_bytes(&rom, CompatibilityMode::CosmacVIP).unwrap();
+        vc.set_quirks(Quirks {
+            clip_sprites,
+            ..vc.quirks()
+        });
+
+        for _ in 0..4 {
+            step(&mut vc);
+        }
+
+        assert_eq!(vc.display()[0][..3], expected);
+    }
+
+    #[test]
+    fn high_resolution_draws_16x16_sprites() {
+        // Switch to 128x64 and draw the big "0" at (120, 0) with DXY0
+        let vc = run_program(
+            &[0x00FF, 0x6078, 0x6100, 0xF230, 0xD010],
+            CompatibilityMode::SuperChip11,
+        );
+
+        assert!(vc.display().is_high_resolution());
+        // Only the left half of the sprite fits before it is clipped at the right edge
+        assert_eq!(vc.display()[0][120..], [1; 8]);
+        assert_eq!(vc.display()[2][120..], [1, 1, 0, 0, 0, 0, 1, 1]);
+    }
+
+    #[test]
+    fn scroll_down_moves_pixels() {
+        let vc = run_program(
+            &[0x6000, 0xF029, 0xD005, 0x00C2],
+            CompatibilityMode::SuperChip11,
+        );
+
+        assert!(vc.display()[0].iter().all(|&pixel| pixel == 0));
+        assert_eq!(vc.display()[2][..5], [1, 1, 1, 1, 0]);
+    }
+
+    #[test]
+    fn rpl_flags_round_trip_registers() {
+        let vc = run_program(
+            &[0x6011, 0x6122, 0xF175, 0x6000, 0x6100, 0xF185],
+            CompatibilityMode::SuperChip11,
+        );
+
+        assert_eq!(vc.variable_registers[..2], [0x11, 0x22]);
+    }
+
+    #[test]
+    fn exit_stops_fetching_instructions() {
+        let mut vc = run_program(&[0x00FD], CompatibilityMode::SuperChip11);
+
+        assert!(vc.has_exited());
+        step(&mut vc);
+        assert_eq!(vc.program_counter, 0x202);
+    }
+
+    #[test]
+    fn both_planes_draw_consecutive_sprites() {
+        // Select both planes, point I at the "0" and "1" font characters, and draw 5 rows of
+        // each into the first and second plane respectively
+        let vc = run_program(&[0xF301, 0x6000, 0xF029, 0xD005], CompatibilityMode::XoChip);
+
+        // "0" starts with 0xF0 and "1" with 0x20
+        assert_eq!(vc.display()[0][..4], [1, 1, 3, 1]);
+    }
+
+    #[test]
+    fn register_ranges_store_and_load_in_either_order() {
+        let vc = run_program(
+            &[0x6011, 0x6122, 0x6233, 0xA300, 0x5022, 0xA301, 0x5203],
+            CompatibilityMode::XoChip,
+        );
+
+        assert_eq!(vc.memory[0x300..0x303], [0x11, 0x22, 0x33]);
+        // Loading V2 down to V0 from 0x301 reads 0x22, 0x33, 0x00
+        assert_eq!(vc.variable_registers[..3], [0x00, 0x33, 0x22]);
+        assert_eq!(vc.index_register, 0x301);
+    }
+
+    #[test]
+    fn long_index_load_reads_following_word() {
+        let mut vc =
+            VirtualComputer::from_rom_bytes(&[0xF0, 0x00, 0xBE, 0xEF], CompatibilityMode::XoChip)
+                .unwrap();
+        step(&mut vc);
+
+        assert_eq!(vc.index_register, 0xBEEF);
+        assert_eq!(vc.program_counter, 0x204);
+    }
+
+    #[test]
+    fn skips_jump_over_long_index_load() {
+        // V0 == 0, so skip the F000 NNNN that follows
+        let mut vc = VirtualComputer::from_rom_bytes(
+            &[0x30, 0x00, 0xF0, 0x00, 0x12, 0x34],
+            CompatibilityMode::XoChip,
+        )
+        .unwrap();
+        step(&mut vc);
+
+        assert_eq!(vc.program_counter, 0x206);
+    }
+
+    #[test]
+    fn xo_chip_has_64k_of_memory() {
+        assert!(VirtualComputer::from_rom_bytes(&[0; 0x1000], CompatibilityMode::XoChip).is_ok());
+    }
+
+    #[test]
+    fn run_frame_executes_instructions_then_ticks_timers_once() {
+        // V0 = 5, DT = V0, then count up in V1 forever
+        let rom = [0x60, 0x05, 0xF0, 0x15, 0x71, 0x01, 0x12, 0x04];
+        let mut vc = VirtualComputer::from_rom_bytes(&rom, CompatibilityMode::CosmacVIP).unwrap();
+
+        vc.run_frame(10).unwrap();
+
+        // 2 setup instructions, then 4 trips around the loop
+        assert_eq!(vc.variable_registers[1], 4);
+        assert_eq!(vc.delay_timer, 4);
+    }
+
+    #[test]
+    fn same_seed_generates_same_numbers() {
+        let rom = [0xC0, 0xFF, 0xC1, 0xFF, 0xC2, 0xFF];
+        let run = |seed| {
+            let mut vc =
+                VirtualComputer::from_rom_bytes(&rom, CompatibilityMode::CosmacVIP).unwrap();
+            vc.set_seed(seed);
+            vc.run_frame(3).unwrap();
+            vc.variable_registers
+        };
+
+        assert_eq!(run(1234), run(1234));
+        assert_ne!(run(1234), run(5678));
+    }
+
+    #[test]
+    fn loaded_state_continues_identically() {
+        // Draw a random-width line of random numbers, forever
+        let rom = [0xC0, 0xFF, 0xA0, 0x00, 0xF0, 0x55, 0xD0, 0x11, 0x12, 0x00];
+        let mut vc = VirtualComputer::from_rom_bytes(&rom, CompatibilityMode::XoChip).unwrap();
+        vc.set_seed(42);
+        vc.run_frame(7).unwrap();
+
+        let mut restored = VirtualComputer::load_state(&vc.save_state()).unwrap();
+        assert_eq!(restored.save_state(), vc.save_state());
+
+        vc.run_frame(20).unwrap();
+        restored.run_frame(20).unwrap();
+        assert_eq!(restored.save_state(), vc.save_state());
+        assert_eq!(restored.display(), vc.display());
+    }
+
+    #[rstest]
+    #[case::not_a_state(b"CHIP8 ROM".to_vec())]
+    #[case::other_version([b"C8ST".as_slice(), &[2, 0]].concat())]
+    #[case::truncated(VirtualComputer::default().save_state()[..100].to_vec())]
+    fn invalid_states_are_rejected(#[case] state: Vec<u8>) {
+        assert!(VirtualComputer::load_state(&state).is_err());
+    }
+
+    #[test]
+    fn rom_larger_than_memory_is_rejected() {
+        assert_eq!(
+            VirtualComputer::from_rom_bytes(&[0; 4096 - 0x200 + 1], CompatibilityMode::CosmacVIP)
+                .err(),
+            Some(Chip8Error::RomTooLarge {
+                size: 3585,
+                max_size: 3584
+            })
+        );
+    }
+
+    #[rstest]
+    #[case::unknown_opcode(&[0x6000, 0x0000], Chip8Error::UnknownOpcode { opcode: 0x0000, address: 0x202 })]
+    #[case::stack_underflow(&[0x00EE], Chip8Error::StackUnderflow { address: 0x200 })]
+    #[case::stack_overflow(&[0x2200], Chip8Error::StackOverflow { address: 0x200 })]
+    #[case::invalid_key(&[0x6010, 0xE09E], Chip8Error::InvalidKey { key: 0x10, address: 0x202 })]
+    #[case::memory_out_of_bounds(&[0xAFFF, 0xF155], Chip8Error::MemoryOutOfBounds { address: 0x1000 })]
+    fn faults_are_returned_and_leave_pc_on_the_instruction(
+        #[case] program: &[u16],
+        #[case] expected: Chip8Error,
+    ) {
+        let rom: Vec<u8> = program
+            .iter()
+            .flat_map(|instr| instr.to_be_bytes())
+            .collect();
+        let mut vc = VirtualComputer::from_rom_bytes(&rom, CompatibilityMode::Chip48).unwrap();
+
+        let fault = (0..100).find_map(|_| vc.step().err()).unwrap();
+
+        assert_eq!(fault, expected);
+        let faulting_address = 0x200 + 2 * (program.len() as u16 - 1);
+        assert_eq!(vc.program_counter, faulting_address);
+    }
+
+    #[rstest]
+    #[case::scroll_down(0x00C1)]
+    #[case::scroll_right(0x00FB)]
+    #[case::scroll_left(0x00FC)]
+    #[case::exit(0x00FD)]
+    #[case::low_resolution(0x00FE)]
+    #[case::high_resolution(0x00FF)]
+    #[case::big_sprite(0xD120)]
+    #[case::big_font(0xF130)]
+    #[case::long_index(0xF000)]
+    #[case::store_range(0x5012)]
+    #[case::load_range(0x5013)]
+    #[case::select_planes(0xF201)]
+    #[case::audio_pattern(0xF002)]
+    #[case::pitch(0xF03A)]
+    #[case::scroll_up(0x00D1)]
+    #[case::save_flags(0xF175)]
+    #[case::load_flags(0xF185)]
+    fn extension_opcodes_are_unknown_to_the_cosmac_vip(#[case] opcode: u16) {
+        let mut vc =
+            VirtualComputer::from_rom_bytes(&opcode.to_be_bytes(), CompatibilityMode::CosmacVIP)
+                .unwrap();
+
+        assert_eq!(
+            vc.step(),
+            Err(Chip8Error::UnknownOpcode {
+                opcode,
+                address: 0x200
+            })
+        );
+    }
+
+    #[rstest]
+    #[case::scroll_down(0x00C1)]
+    #[case::scroll_right(0x00FB)]
+    #[case::scroll_left(0x00FC)]
+    fn scrolling_is_unknown_to_super_chip_1_0(#[case] opcode: u16) {
+        let mut vc =
+            VirtualComputer::from_rom_bytes(&opcode.to_be_bytes(), CompatibilityMode::SuperChip10)
+                .unwrap();
+
+        assert_eq!(
+            vc.step(),
+            Err(Chip8Error::UnknownOpcode {
+                opcode,
+                address: 0x200
+            })
+        );
+    }
+
+    #[rstest]
+    #[case(CompatibilityMode::CosmacVIP, Ok(()))]
+    #[case(CompatibilityMode::SuperChip11, Err(Chip8Error::MemoryOutOfBounds { address: 0x1000 }))]
+    fn addresses_past_the_end_of_memory_wrap_or_fault(
+        #[case] mode: CompatibilityMode,
+        #[case] expected: Result<(), Chip8Error>,
+    ) {
+        // Store V0 and V1 at 0xFFF, which straddles the end of memory
+        let rom = [0x60, 0xAB, 0x61, 0xCD, 0xAF, 0xFF, 0xF1, 0x55];
+        let mut vc = VirtualComputer::from_rom_bytes(&rom, mode).unwrap();
+        vc.set_quirks(Quirks {
+            memory_increments_index: false,
+            ..vc.quirks()
+        });
+
+        for _ in 0..3 {
+            step(&mut vc);
+        }
+
+        assert_eq!(vc.step(), expected);
+        if expected.is_ok() {
+            assert_eq!(vc.memory[0xFFF], 0xAB);
+            assert_eq!(vc.memory[0x000], 0xCD);
+        }
+    }
+
+    #[test]
+    fn display_wait_at_the_end_of_memory_retries_the_same_instruction() {
+        let mut vc = VirtualComputer::new(CompatibilityMode::XoChip);
+        vc.set_quirks(Quirks {
+            display_wait: true,
+            ..vc.quirks()
+        });
+        vc.memory[0xFFFE..].copy_from_slice(&[0xD0, 0x01]);
+        vc.program_counter = 0xFFFE;
+        vc.vblank_ready = false;
+
+        step(&mut vc);
+
+        assert_eq!(vc.program_counter, 0xFFFE);
+    }
+
+    #[rstest]
+    fn random_programs_never_panic(
+        #[values(
+            CompatibilityMode::CosmacVIP,
+            CompatibilityMode::Chip48,
+            CompatibilityMode::SuperChip10,
+            CompatibilityMode::SuperChip11,
+            CompatibilityMode::XoChip
+        )]
+        mode: CompatibilityMode,
+    ) {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        for _ in 0..200 {
+            let rom: Vec<u8> = (0..64).map(|_| rng.gen()).collect();
+            let mut vc = VirtualComputer::from_rom_bytes(&rom, mode).unwrap();
+            for _ in 0..100 {
+                if vc.step().is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    #[rstest]
+    #[case(CompatibilityMode::CosmacVIP, 12)]
+    #[case(CompatibilityMode::SuperChip11, 16)]
+    #[case(CompatibilityMode::XoChip, 16)]
+    fn stack_overflows_at_the_profiles_depth(
+        #[case] mode: CompatibilityMode,
+        #[case] expected_depth: usize,
+    ) {
+        // Calls itself forever
+        let mut vc = VirtualComputer::from_rom_bytes(&[0x22, 0x00], mode).unwrap();
+
+        let fault = (0..100).find_map(|_| vc.step().err()).unwrap();
+
+        assert_eq!(fault, Chip8Error::StackOverflow { address: 0x200 });
+        assert_eq!(vc.stack().len(), expected_depth);
+    }
+
+    #[test]
+    fn skipping_a_fault_carries_on_with_the_next_instruction() {
+        let rom = [0x00, 0xEE, 0x60, 0x42];
+        let mut vc = VirtualComputer::from_rom_bytes(&rom, CompatibilityMode::CosmacVIP).unwrap();
+
+        assert_eq!(
+            vc.step(),
+            Err(Chip8Error::StackUnderflow { address: 0x200 })
+        );
+        vc.skip_faulting_instruction();
+        step(&mut vc);
+
+        assert_eq!(vc.variable_registers[0], 0x42);
+    }
+
+    #[test]
+    fn wait_for_key_needs_a_press_and_release_while_timers_tick() {
+        // Start the delay timer, then wait for a key in V1
+        let rom = [0x60, 0x05, 0xF0, 0x15, 0xF1, 0x0A];
+        let mut vc = VirtualComputer::from_rom_bytes(&rom, CompatibilityMode::CosmacVIP).unwrap();
+
+        // Held from before the wait started, so it doesn't count
+        vc.press_key(KeyPress::Key3);
+        vc.run_frame(10).unwrap();
+        vc.release_key(KeyPress::Key3);
+        vc.run_frame(10).unwrap();
+        assert_eq!(vc.program_counter, 0x204);
+        assert_eq!(vc.delay_timer, 3);
+
+        vc.press_key(KeyPress::KeyB);
+        vc.run_frame(10).unwrap();
+        assert_eq!(vc.program_counter, 0x204);
+
+        vc.release_key(KeyPress::KeyB);
+        step(&mut vc);
+        assert_eq!(vc.program_counter, 0x206);
+        assert_eq!(vc.variable_registers[1], 0xB);
+    }
+
+    #[test]
+    fn pressing_a_held_key_again_doesnt_satisfy_wait_for_key() {
+        let rom = [0xF1, 0x0A];
+        let mut vc = VirtualComputer::from_rom_bytes(&rom, CompatibilityMode::CosmacVIP).unwrap();
+
+        vc.press_key(KeyPress::Key3);
+        vc.run_frame(10).unwrap();
+        // What the host's key repeat looks like, with no release in between
+        vc.press_key(KeyPress::Key3);
+        vc.run_frame(10).unwrap();
+        vc.release_key(KeyPress::Key3);
+        vc.run_frame(10).unwrap();
+
+        assert_eq!(vc.program_counter, 0x200);
+        assert_eq!(vc.key_wait, KeyWait::WaitingForPress);
+    }
+
+    #[test]
+    fn set_keypad_presses_and_releases_the_difference() {
+        let mut vc = VirtualComputer::new(CompatibilityMode::CosmacVIP);
+        vc.press_key(KeyPress::Key1);
+        vc.press_key(KeyPress::Key2);
+
+        vc.set_keypad(0b1100);
+
+        assert_eq!(vc.keypad(), 0b1100);
+        assert!(!vc.is_key_down(KeyPress::Key1));
+        assert!(vc.is_key_down(KeyPress::Key3));
+    }
 }